@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display};
 
 #[derive(Debug)]
@@ -13,7 +13,7 @@ pub enum FilterOp {
     LessThanEquals,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Guid(String);
 
 impl Guid {
@@ -40,14 +40,37 @@ pub trait FilterValue: ToString {
     }
 }
 
-impl FilterValue for String {}
+impl FilterValue for String {
+    fn serialize(&self) -> String {
+        quote_string(self)
+    }
+}
 impl FilterValue for Guid {
     fn serialize(&self) -> String {
         Guid::serialize(self)
     }
 }
-impl<'a> FilterValue for &'a str {}
-impl<'a, T: FilterValue> FilterValue for &'a T where &'a T: ToString {}
+impl FilterValue for &str {
+    fn serialize(&self) -> String {
+        quote_string(self)
+    }
+}
+impl FilterValue for i32 {}
+impl<'a, T: FilterValue> FilterValue for &'a T
+where
+    &'a T: ToString,
+{
+    fn serialize(&self) -> String {
+        (*self).serialize()
+    }
+}
+
+/// Quote a string value for use in an OData filter.
+/// Single quotes within the value are escaped by doubling them.
+#[inline]
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
 
 pub struct Bool(bool);
 
@@ -215,6 +238,18 @@ mod test {
         assert_eq!(s, "Bar+eq+'bar'");
     }
 
+    #[test]
+    fn escape_quote() {
+        let s = Filter::new(TestKeys::Bar, "b'ar", FilterOp::Equals).finalize();
+        assert_eq!(s, "Bar+eq+'b''ar'");
+    }
+
+    #[test]
+    fn number() {
+        let s = Filter::new(TestKeys::Foo, 42, FilterOp::Equals).finalize();
+        assert_eq!(s, "Foo+eq+42");
+    }
+
     #[test]
     fn ne() {
         let s = Filter::new(TestKeys::Bar, "bar", FilterOp::NotEqual).finalize();
@@ -0,0 +1,12 @@
+use crate::cache::CodeEntity;
+use crate::{ExactClient, ExactError};
+use exact_filter::Guid;
+
+/// Get the ID of an account, e.g. a customer, by its code.
+/// Exact pads account codes with leading spaces, the code is matched without them.
+pub async fn get_account_by_code<S: AsRef<str>>(
+    client: &ExactClient,
+    code: S,
+) -> Result<Guid, ExactError> {
+    client.resolve_code(CodeEntity::Account, code).await
+}
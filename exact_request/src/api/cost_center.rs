@@ -1,27 +1,32 @@
-use crate::{ExactClient, ExactError, ExactPayload};
+use crate::cache::CodeEntity;
+use crate::{ExactClient, ExactError};
 use exact_filter::Guid;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub async fn get_cost_center_by_code<S: AsRef<str>>(
     client: &ExactClient,
     code: S,
 ) -> Result<Guid, ExactError> {
-    #[derive(Deserialize)]
-    struct Response {
-        #[serde(rename = "ID")]
-        id: Guid,
-    }
+    client.resolve_code(CodeEntity::CostCenter, code).await
+}
 
-    let response: ExactPayload<Response> = client
-        .get(client.divisioned_url(format!(
-            "/hrm/Costcenters?$filter=Code+eq+'{}'&$select=ID",
-            code.as_ref()
-        ))?)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CostCenter {
+    #[serde(rename = "ID")]
+    pub id: Guid,
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "Description")]
+    pub description: String,
+    #[serde(rename = "Active")]
+    pub active: bool,
+}
 
-    Ok(response.value().id)
+/// List all cost centers in the division.
+pub async fn list_cost_centers(client: &ExactClient) -> Result<Vec<CostCenter>, ExactError> {
+    client
+        .list_paginated(
+            client.divisioned_url("/hrm/Costcenters?$select=ID,Code,Description,Active")?,
+        )
+        .await
 }
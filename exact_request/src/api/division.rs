@@ -0,0 +1,37 @@
+use crate::{ExactClient, ExactError};
+use serde::{Deserialize, Serialize};
+
+/// A division, also known as 'Administratie'.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Division {
+    /// The division number, as used in the URLs of the API
+    #[serde(rename = "Code")]
+    pub code: i32,
+    #[serde(rename = "Description")]
+    pub description: String,
+    /// Whether this is the main division of the license
+    #[serde(rename = "Main")]
+    pub main: bool,
+    /// `0` if the division is not blocked.
+    /// Blocked divisions, e.g. archived or awaiting payment, can't be booked in
+    #[serde(rename = "BlockingStatus")]
+    pub blocking_status: i32,
+}
+
+impl Division {
+    pub fn is_blocked(&self) -> bool {
+        self.blocking_status != 0
+    }
+}
+
+/// List all divisions accessible to the current user, across all licenses the user has access to.
+/// Requires a division to be set on the client, any accessible division will do.
+pub async fn list_divisions(client: &ExactClient) -> Result<Vec<Division>, ExactError> {
+    client
+        .list_paginated(
+            client.divisioned_url(
+                "/system/AllDivisions?$select=Code,Description,Main,BlockingStatus",
+            )?,
+        )
+        .await
+}
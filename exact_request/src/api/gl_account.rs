@@ -1,27 +1,36 @@
-use crate::{ExactClient, ExactError, ExactPayload};
+use crate::cache::CodeEntity;
+use crate::{ExactClient, ExactError};
 use exact_filter::Guid;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub async fn get_gl_account_by_code<S: AsRef<str>>(
     client: &ExactClient,
     code: S,
 ) -> Result<Guid, ExactError> {
-    #[derive(Deserialize)]
-    struct Response {
-        #[serde(rename = "ID")]
-        id: Guid,
-    }
+    client.resolve_code(CodeEntity::GLAccount, code).await
+}
 
-    let response: ExactPayload<Response> = client
-        .get(client.divisioned_url(format!(
-            "/financial/GLAccounts?$filter=Code+eq+'{}'&$select=ID",
-            code.as_ref()
-        ))?)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GLAccount {
+    #[serde(rename = "ID")]
+    pub id: Guid,
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "Description")]
+    pub description: String,
+    /// The type of the account, e.g. `Revenue`
+    #[serde(rename = "TypeDescription")]
+    pub type_description: String,
+    /// `B` for balance sheet accounts, `W` for profit and loss accounts
+    #[serde(rename = "BalanceType")]
+    pub balance_type: String,
+}
 
-    Ok(response.value().id)
+/// List all GL accounts in the division.
+pub async fn list_gl_accounts(client: &ExactClient) -> Result<Vec<GLAccount>, ExactError> {
+    client
+        .list_paginated(client.divisioned_url(
+            "/financial/GLAccounts?$select=ID,Code,Description,TypeDescription,BalanceType",
+        )?)
+        .await
 }
@@ -0,0 +1,26 @@
+use crate::{ExactClient, ExactError};
+use exact_filter::Guid;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Journal {
+    #[serde(rename = "ID")]
+    pub id: Guid,
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "Description")]
+    pub description: String,
+    /// The type of the journal.
+    /// `10` cash, `12` bank, `20` sales, `22` purchase, `90` general journal.
+    #[serde(rename = "Type")]
+    pub journal_type: i32,
+}
+
+/// List all journals in the division.
+pub async fn list_journals(client: &ExactClient) -> Result<Vec<Journal>, ExactError> {
+    client
+        .list_paginated(
+            client.divisioned_url("/financial/Journals?$select=ID,Code,Description,Type")?,
+        )
+        .await
+}
@@ -1,8 +1,8 @@
 use serde::Deserialize;
 
-use crate::{ExactClient, ExactPayload};
+use crate::{ExactClient, ExactError, ExactPayload, ExactResponse};
 
-pub async fn accounting_division(client: &ExactClient) -> Result<i32, reqwest::Error> {
+pub async fn accounting_division(client: &ExactClient) -> Result<i32, ExactError> {
     #[derive(Deserialize)]
     #[serde(rename_all = "PascalCase")]
     struct Response {
@@ -10,14 +10,13 @@ pub async fn accounting_division(client: &ExactClient) -> Result<i32, reqwest::E
     }
 
     let r: ExactPayload<Response> = client
-        .get(ExactClient::url(
-            "/api/v1/current/Me?$select=AccountingDivision",
-        ))
+        .get(client.url("/api/v1/current/Me?$select=AccountingDivision"))
         .send()
         .await?
-        .error_for_status()?
+        .exact_error_for_status()
+        .await?
         .json()
         .await?;
 
-    Ok(r.value().accounting_division)
+    Ok(r.value("current user", "Me")?.accounting_division)
 }
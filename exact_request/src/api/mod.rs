@@ -1,5 +1,10 @@
+pub mod account;
 pub mod cost_center;
+pub mod division;
 pub mod gl_account;
+pub mod journal;
 pub mod me;
 pub mod oauth;
 pub mod sales_entry;
+pub mod transaction_line;
+pub mod vat_code;
@@ -1,12 +1,12 @@
-use crate::ExactClient;
+use crate::ExactRegion;
 use serde::{Deserialize, Serialize};
 
-pub fn login_url<S1, S2>(client_id: S1, redirect_uri: S2) -> String
+pub fn login_url<S1, S2>(client_id: S1, redirect_uri: S2, region: &ExactRegion) -> String
 where
     S1: AsRef<str>,
     S2: AsRef<str>,
 {
-    ExactClient::url(format!(
+    region.url(format!(
         "/api/oauth2/auth?client_id={}&redirect_uri={}&response_type=code&force_login=0",
         client_id.as_ref(),
         redirect_uri.as_ref()
@@ -24,6 +24,7 @@ pub async fn exchange_code<S1, S2, S3>(
     client_id: S1,
     client_secret: S2,
     redirect_uri: S3,
+    region: &ExactRegion,
 ) -> Result<OAuthTokenPair, reqwest::Error>
 where
     S1: AsRef<str>,
@@ -40,7 +41,7 @@ where
     }
 
     reqwest::Client::new()
-        .post(ExactClient::url("/api/oauth2/token"))
+        .post(region.url("/api/oauth2/token"))
         .form(&RequestForm {
             code: &code,
             grant_type: "authorization_code",
@@ -1,6 +1,9 @@
-use crate::{ExactClient, ExactError, ExactPayload};
+use crate::batch::{Batch, BatchResponse, ChangeSet};
+use crate::{ExactClient, ExactCreated, ExactError, ExactPayload, ExactResponse};
 use exact_filter::{Filter, FilterOp, Guid};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
+use time::macros::format_description;
+use time::Date;
 
 pub async fn get_sales_entry_for_entry_number(
     client: &ExactClient,
@@ -14,32 +17,36 @@ pub async fn get_sales_entry_for_entry_number(
 
     let response: ExactPayload<Response> = client
         .get(client.divisioned_url(format!(
-            "/salesentry/SalesEntries?$filter=EntryNumber+eq+{number}&$select=EntryID"
+            "/salesentry/SalesEntries?$filter={}&$select=EntryID",
+            Filter::new("EntryNumber", number, FilterOp::Equals).finalize()
         ))?)
         .send()
         .await?
-        .error_for_status()?
+        .exact_error_for_status()
+        .await?
         .json()
         .await?;
 
-    Ok(response.value().entry_id)
+    Ok(response
+        .value("sales entry", format!("EntryNumber eq {number}"))?
+        .entry_id)
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SalesEntryLine {
     #[serde(rename = "ID")]
-    id: Guid,
+    pub id: Guid,
     /// The value of the line excluding VAT
     #[serde(rename = "AmountFC")]
-    amount_fc: f32,
+    pub amount_fc: f32,
     #[serde(rename = "VATCode")]
-    vat_code: String,
+    pub vat_code: String,
     #[serde(rename = "VATPercentage")]
-    vat_percentage: f32,
+    pub vat_percentage: f32,
     #[serde(rename = "CostCenter")]
-    cost_center: Option<String>,
+    pub cost_center: Option<String>,
     #[serde(rename = "Description")]
-    description: String,
+    pub description: String,
 }
 
 pub async fn get_sales_entry_lines(
@@ -53,9 +60,148 @@ pub async fn get_sales_entry_lines(
         )?)
         .send()
         .await?
-        .error_for_status()?
+        .exact_error_for_status()
+        .await?
         .json()
         .await?;
 
     Ok(response.values())
 }
+
+/// A line to add to a sales entry.
+#[derive(Debug, Serialize)]
+pub struct NewSalesEntryLine {
+    /// The entry to add the line to.
+    /// Must be `None` when the line is part of a [NewSalesEntry].
+    #[serde(rename = "EntryID", skip_serializing_if = "Option::is_none")]
+    pub entry_id: Option<Guid>,
+    #[serde(rename = "GLAccount")]
+    pub gl_account: Guid,
+    /// The value of the line excluding VAT
+    #[serde(rename = "AmountFC")]
+    pub amount_fc: f32,
+    #[serde(rename = "VATCode", skip_serializing_if = "Option::is_none")]
+    pub vat_code: Option<String>,
+    /// The VAT of the line.
+    /// If `None`, Exact computes it from the VAT code
+    #[serde(rename = "VATAmountFC", skip_serializing_if = "Option::is_none")]
+    pub vat_amount_fc: Option<f32>,
+    /// The code of the cost center
+    #[serde(rename = "CostCenter", skip_serializing_if = "Option::is_none")]
+    pub cost_center: Option<String>,
+    /// The code of the cost unit
+    #[serde(rename = "CostUnit", skip_serializing_if = "Option::is_none")]
+    pub cost_unit: Option<String>,
+    #[serde(rename = "Description")]
+    pub description: String,
+}
+
+/// Add lines to existing sales entries, in one `$batch` request.
+/// The lines are added in one changeset, so either all lines are added or none are.
+/// Returns the IDs of the created lines, in order.
+///
+/// # Errors
+///
+/// - If the request fails
+/// - If Exact rejects any of the lines
+pub async fn create_sales_entry_lines(
+    client: &ExactClient,
+    lines: &[NewSalesEntryLine],
+) -> Result<Vec<Guid>, ExactError> {
+    #[derive(Deserialize)]
+    struct Response {
+        #[serde(rename = "ID")]
+        id: Guid,
+    }
+
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let url = client.divisioned_url("/salesentry/SalesEntryLines")?;
+    let mut changeset = ChangeSet::new();
+    for line in lines {
+        changeset.post(&url, line)?;
+    }
+    let mut batch = Batch::new();
+    batch.changeset(changeset);
+
+    let responses = match client.batch(&batch).await?.pop() {
+        Some(BatchResponse::ChangeSet(responses)) => responses,
+        _ => {
+            return Err(ExactError::InvalidBatch(
+                "no response to the changeset".to_string(),
+            ))
+        }
+    };
+
+    let ids = responses
+        .into_iter()
+        .map(|response| {
+            let created: ExactCreated<Response> = response.error_for_status()?.json()?;
+            Ok(created.value().id)
+        })
+        .collect::<Result<Vec<_>, ExactError>>()?;
+
+    if ids.len() != lines.len() {
+        return Err(ExactError::InvalidBatch(format!(
+            "{} lines created, expected {}",
+            ids.len(),
+            lines.len()
+        )));
+    }
+
+    Ok(ids)
+}
+
+/// A sales entry to create, including its lines.
+#[derive(Debug, Serialize)]
+pub struct NewSalesEntry {
+    /// The code of the sales journal
+    #[serde(rename = "Journal")]
+    pub journal: String,
+    /// The ID of the customer account
+    #[serde(rename = "Customer")]
+    pub customer: Guid,
+    #[serde(rename = "EntryDate", serialize_with = "serialize_date")]
+    pub entry_date: Date,
+    #[serde(rename = "Description")]
+    pub description: String,
+    #[serde(rename = "SalesEntryLines")]
+    pub lines: Vec<NewSalesEntryLine>,
+}
+
+/// A sales entry created in Exact.
+#[derive(Debug, Deserialize)]
+pub struct CreatedSalesEntry {
+    #[serde(rename = "EntryID")]
+    pub entry_id: Guid,
+    #[serde(rename = "EntryNumber")]
+    pub entry_number: i32,
+}
+
+/// Create a sales entry, header and lines, in one deep insert.
+pub async fn create_sales_entry(
+    client: &ExactClient,
+    entry: &NewSalesEntry,
+) -> Result<CreatedSalesEntry, ExactError> {
+    let response: ExactCreated<CreatedSalesEntry> = client
+        .post(client.divisioned_url("/salesentry/SalesEntries")?)
+        .json(entry)
+        .send()
+        .await?
+        .exact_error_for_status()
+        .await?
+        .json()
+        .await?;
+
+    Ok(response.value())
+}
+
+/// Serialize a date as the local date-time format accepted by Exact.
+fn serialize_date<S: Serializer>(date: &Date, serializer: S) -> Result<S::Ok, S::Error> {
+    let formatted = date
+        .format(format_description!("[year]-[month]-[day]T00:00:00"))
+        .map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&formatted)
+}
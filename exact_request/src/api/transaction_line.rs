@@ -0,0 +1,55 @@
+use crate::sync::SyncCursor;
+use crate::{ExactClient, ExactError};
+use exact_filter::{Filter, Guid};
+use serde::{Deserialize, Serialize};
+
+/// The fields of a transaction line that are read.
+const SELECT: &str = "ID,EntryNumber,JournalCode,GLAccountCode,CostCenter,Description,AmountDC,FinancialYear,FinancialPeriod";
+
+/// A line of a booked financial transaction, in any journal.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TransactionLine {
+    #[serde(rename = "ID")]
+    pub id: Guid,
+    #[serde(rename = "EntryNumber")]
+    pub entry_number: i32,
+    #[serde(rename = "JournalCode")]
+    pub journal_code: String,
+    #[serde(rename = "GLAccountCode")]
+    pub gl_account_code: String,
+    /// The code of the cost center
+    #[serde(rename = "CostCenter")]
+    pub cost_center: Option<String>,
+    #[serde(rename = "Description")]
+    pub description: Option<String>,
+    /// The amount in the currency of the division, positive for debit
+    #[serde(rename = "AmountDC")]
+    pub amount_dc: f32,
+    #[serde(rename = "FinancialYear")]
+    pub financial_year: i32,
+    #[serde(rename = "FinancialPeriod")]
+    pub financial_period: i32,
+}
+
+/// List all transaction lines changed since the last sync, see [ExactClient::sync].
+pub async fn sync_transaction_lines(
+    client: &ExactClient,
+    cursor: &mut SyncCursor,
+) -> Result<Vec<TransactionLine>, ExactError> {
+    client
+        .sync("Financial/TransactionLines", SELECT, cursor)
+        .await
+}
+
+/// List all transaction lines matching `filter`, 1000 per request.
+pub async fn list_transaction_lines(
+    client: &ExactClient,
+    filter: Filter,
+) -> Result<Vec<TransactionLine>, ExactError> {
+    client
+        .list_paginated(client.divisioned_url(format!(
+            "/bulk/Financial/TransactionLines?$select={SELECT}&$filter={}",
+            filter.finalize()
+        ))?)
+        .await
+}
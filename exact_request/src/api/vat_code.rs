@@ -0,0 +1,48 @@
+use crate::{ExactClient, ExactError};
+use exact_filter::Guid;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VATCode {
+    #[serde(rename = "ID")]
+    pub id: Guid,
+    /// The code, padded with spaces by Exact, e.g. `  2`
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "Description")]
+    pub description: String,
+    /// The VAT percentage as a fraction, e.g. `0.21`
+    #[serde(rename = "Percentage")]
+    pub percentage: f32,
+    /// How amounts relate to the VAT.
+    /// `B` for amounts excluding VAT, `I` for amounts including VAT, `N` for no VAT
+    #[serde(rename = "Type")]
+    pub vat_type: String,
+    /// Where the code may be used.
+    /// `S` for sales, `P` for purchases, `B` for both
+    #[serde(rename = "VATTransactionType")]
+    pub transaction_type: String,
+    #[serde(rename = "IsBlocked")]
+    pub is_blocked: bool,
+}
+
+impl VATCode {
+    /// Whether the code may be used for sales.
+    pub fn is_sales(&self) -> bool {
+        self.transaction_type.eq("S") || self.transaction_type.eq("B")
+    }
+
+    /// Whether amounts booked with the code include the VAT.
+    pub fn is_inclusive(&self) -> bool {
+        self.vat_type.eq("I")
+    }
+}
+
+/// List all VAT codes in the division.
+pub async fn list_vat_codes(client: &ExactClient) -> Result<Vec<VATCode>, ExactError> {
+    client
+        .list_paginated(client.divisioned_url(
+            "/vat/VATCodes?$select=ID,Code,Description,Percentage,Type,VATTransactionType,IsBlocked",
+        )?)
+        .await
+}
@@ -0,0 +1,441 @@
+use crate::{ExactClient, ExactError, ExactResponse};
+use log::debug;
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+/// Several requests sent to Exact as one OData `$batch` request.
+///
+/// Each part of the batch is either a single operation,
+/// or a [ChangeSet] of which all operations succeed or none do.
+#[derive(Debug, Default)]
+pub struct Batch {
+    parts: Vec<BatchPart>,
+}
+
+#[derive(Debug)]
+enum BatchPart {
+    Operation(Operation),
+    ChangeSet(ChangeSet),
+}
+
+/// Operations that are applied all together, or not at all.
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    operations: Vec<Operation>,
+}
+
+/// A request within a batch.
+#[derive(Debug)]
+struct Operation {
+    method: Method,
+    url: String,
+    body: Option<String>,
+}
+
+/// The response to one operation in a batch.
+#[derive(Debug)]
+pub struct OperationResponse {
+    pub status: StatusCode,
+    /// The `X-Request-Id` of the response, if Exact included one
+    pub request_id: Option<String>,
+    pub body: String,
+}
+
+/// The responses to one part of a [Batch], in the order the parts were added.
+#[derive(Debug)]
+pub enum BatchResponse {
+    Operation(OperationResponse),
+    /// The responses to the operations of the changeset, in order.
+    /// If the changeset failed, this is only the response describing the failure.
+    ChangeSet(Vec<OperationResponse>),
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a `GET` request. Pass the URL as formatted by [ExactClient::divisioned_url].
+    pub fn get<S: AsRef<str>>(&mut self, url: S) {
+        self.parts.push(BatchPart::Operation(Operation {
+            method: Method::GET,
+            url: url.as_ref().to_string(),
+            body: None,
+        }));
+    }
+
+    /// Add a changeset.
+    pub fn changeset(&mut self, changeset: ChangeSet) {
+        self.parts.push(BatchPart::ChangeSet(changeset));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// The multipart body of the batch request.
+    fn body(&self, boundary: &str) -> String {
+        let mut body = String::new();
+        for (idx, part) in self.parts.iter().enumerate() {
+            body.push_str(&format!("--{boundary}\r\n"));
+            match part {
+                BatchPart::Operation(operation) => operation.write(&mut body),
+                BatchPart::ChangeSet(changeset) => {
+                    let changeset_boundary = format!("changeset_{idx}_{boundary}");
+                    body.push_str(&format!(
+                        "Content-Type: multipart/mixed; boundary={changeset_boundary}\r\n\r\n"
+                    ));
+                    for operation in &changeset.operations {
+                        body.push_str(&format!("--{changeset_boundary}\r\n"));
+                        operation.write(&mut body);
+                    }
+                    body.push_str(&format!("--{changeset_boundary}--\r\n"));
+                }
+            }
+        }
+        body.push_str(&format!("--{boundary}--\r\n"));
+
+        body
+    }
+
+    /// Pair the parsed responses with the parts of the batch.
+    /// A failed changeset is answered with a single response, instead of a multipart one.
+    fn match_responses(
+        &self,
+        responses: Vec<BatchResponse>,
+    ) -> Result<Vec<BatchResponse>, ExactError> {
+        if responses.len() != self.parts.len() {
+            return Err(ExactError::InvalidBatch(format!(
+                "{} responses to {} parts",
+                responses.len(),
+                self.parts.len()
+            )));
+        }
+
+        Ok(self
+            .parts
+            .iter()
+            .zip(responses)
+            .map(|(part, response)| match (part, response) {
+                (BatchPart::ChangeSet(_), BatchResponse::Operation(response)) => {
+                    BatchResponse::ChangeSet(vec![response])
+                }
+                (_, response) => response,
+            })
+            .collect())
+    }
+}
+
+impl ChangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a `POST` request creating `value`. Pass the URL as formatted by [ExactClient::divisioned_url].
+    ///
+    /// # Errors
+    ///
+    /// If `value` could not be serialized
+    pub fn post<S: AsRef<str>, T: Serialize>(
+        &mut self,
+        url: S,
+        value: &T,
+    ) -> Result<(), ExactError> {
+        self.operations.push(Operation {
+            method: Method::POST,
+            url: url.as_ref().to_string(),
+            body: Some(serde_json::to_string(value)?),
+        });
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+impl Operation {
+    /// Write the operation as a part of a multipart body.
+    fn write(&self, body: &mut String) {
+        body.push_str("Content-Type: application/http\r\n");
+        body.push_str("Content-Transfer-Encoding: binary\r\n\r\n");
+        body.push_str(&format!("{} {} HTTP/1.1\r\n", self.method, self.url));
+        body.push_str("Accept: application/json\r\n");
+        match &self.body {
+            Some(content) => {
+                body.push_str("Content-Type: application/json\r\n\r\n");
+                body.push_str(content);
+                body.push_str("\r\n");
+            }
+            None => body.push_str("\r\n"),
+        }
+    }
+}
+
+impl OperationResponse {
+    /// Turn a response with an error status into [ExactError::Api].
+    pub fn error_for_status(self) -> Result<Self, ExactError> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            Err(ExactError::from_body(
+                self.status,
+                self.request_id,
+                &self.body,
+            ))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Deserialize the body of the response.
+    ///
+    /// # Errors
+    ///
+    /// If the body is not valid JSON for `T`
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, ExactError> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+}
+
+impl ExactClient {
+    /// Send all requests of the batch in one request.
+    /// Returns one [BatchResponse] per part of the batch.
+    ///
+    /// # Errors
+    ///
+    /// - If the batch request itself fails
+    /// - If the response could not be parsed
+    ///
+    /// Failed operations do not result in an error, check their [OperationResponse::status].
+    pub async fn batch(&self, batch: &Batch) -> Result<Vec<BatchResponse>, ExactError> {
+        let boundary = format!("batch_{}", OffsetDateTime::now_utc().unix_timestamp_nanos());
+        debug!("Sending batch of {} parts to Exact", batch.parts.len());
+
+        let response = self
+            .post(self.divisioned_url("/$batch")?)
+            .header(
+                CONTENT_TYPE,
+                HeaderValue::from_str(&format!("multipart/mixed; boundary={boundary}"))
+                    .expect("Creating batch content type"),
+            )
+            .header(ACCEPT, HeaderValue::from_static("multipart/mixed"))
+            .body(batch.body(&boundary))
+            .send()
+            .await?
+            .exact_error_for_status()
+            .await?;
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = response.text().await?;
+
+        batch.match_responses(parse_batch(&content_type, &body)?)
+    }
+}
+
+/// Parse the multipart response to a batch request.
+fn parse_batch(content_type: &str, body: &str) -> Result<Vec<BatchResponse>, ExactError> {
+    let boundary = multipart_boundary(content_type)?;
+    multipart_parts(body, boundary)?
+        .into_iter()
+        .map(|part| {
+            let (headers, content) = split_headers(part)?;
+            match header(headers, "Content-Type").and_then(|value| multipart_boundary(value).ok()) {
+                Some(changeset_boundary) => Ok(BatchResponse::ChangeSet(
+                    multipart_parts(content, changeset_boundary)?
+                        .into_iter()
+                        .map(|part| parse_operation(split_headers(part)?.1))
+                        .collect::<Result<_, _>>()?,
+                )),
+                None => Ok(BatchResponse::Operation(parse_operation(content)?)),
+            }
+        })
+        .collect()
+}
+
+/// The boundary of a `multipart/mixed` content type.
+fn multipart_boundary(content_type: &str) -> Result<&str, ExactError> {
+    content_type
+        .split(';')
+        .map(str::trim)
+        .find_map(|param| param.strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"'))
+        .filter(|_| content_type.trim_start().starts_with("multipart/mixed"))
+        .ok_or_else(|| ExactError::InvalidBatch(format!("not multipart: {content_type}")))
+}
+
+/// The parts of a multipart body, without the delimiters.
+fn multipart_parts<'a>(body: &'a str, boundary: &str) -> Result<Vec<&'a str>, ExactError> {
+    let delimiter = format!("--{boundary}");
+    let mut sections = body.split(delimiter.as_str());
+
+    // Anything before the first delimiter is a preamble
+    sections.next();
+
+    let mut parts = Vec::new();
+    for section in sections {
+        if section.starts_with("--") {
+            return Ok(parts);
+        }
+        parts.push(section.trim_start_matches(['\r', '\n']));
+    }
+
+    Err(ExactError::InvalidBatch(format!(
+        "missing closing delimiter {delimiter}--"
+    )))
+}
+
+/// Split a part into its headers and its content.
+fn split_headers(part: &str) -> Result<(&str, &str), ExactError> {
+    part.split_once("\r\n\r\n")
+        .or_else(|| part.split_once("\n\n"))
+        .ok_or_else(|| ExactError::InvalidBatch("part without headers".to_string()))
+}
+
+/// The value of a header, matched case insensitively.
+fn header<'a>(headers: &'a str, name: &str) -> Option<&'a str> {
+    headers.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Parse an HTTP response embedded in a multipart body.
+fn parse_operation(content: &str) -> Result<OperationResponse, ExactError> {
+    let (head, body) = split_headers(content).unwrap_or((content, ""));
+    let status_line = head.lines().next().unwrap_or_default();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| ExactError::InvalidBatch(format!("invalid status line: {status_line}")))?;
+
+    Ok(OperationResponse {
+        status,
+        request_id: header(head, "X-Request-Id").map(str::to_string),
+        body: body.trim_end_matches(['\r', '\n']).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batch_body() {
+        let mut changeset = ChangeSet::new();
+        changeset
+            .post("https://exact/api/v1/1/salesentry/SalesEntryLines", &[1])
+            .unwrap();
+        let mut batch = Batch::new();
+        batch.get("https://exact/api/v1/1/financial/Journals");
+        batch.changeset(changeset);
+
+        assert_eq!(
+            "--b\r\n\
+            Content-Type: application/http\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n\
+            GET https://exact/api/v1/1/financial/Journals HTTP/1.1\r\n\
+            Accept: application/json\r\n\r\n\
+            --b\r\n\
+            Content-Type: multipart/mixed; boundary=changeset_1_b\r\n\r\n\
+            --changeset_1_b\r\n\
+            Content-Type: application/http\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n\
+            POST https://exact/api/v1/1/salesentry/SalesEntryLines HTTP/1.1\r\n\
+            Accept: application/json\r\n\
+            Content-Type: application/json\r\n\r\n\
+            [1]\r\n\
+            --changeset_1_b--\r\n\
+            --b--\r\n",
+            batch.body("b")
+        );
+    }
+
+    #[test]
+    fn parse_responses() {
+        let body = "--batchresponse_1\r\n\
+            Content-Type: application/http\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"d\": {\"results\": []}}\r\n\
+            --batchresponse_1\r\n\
+            Content-Type: multipart/mixed; boundary=changesetresponse_2\r\n\r\n\
+            --changesetresponse_2\r\n\
+            Content-Type: application/http\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n\
+            HTTP/1.1 201 Created\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"d\": {\"ID\": \"1\"}}\r\n\
+            --changesetresponse_2\r\n\
+            Content-Type: application/http\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n\
+            HTTP/1.1 201 Created\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"d\": {\"ID\": \"2\"}}\r\n\
+            --changesetresponse_2--\r\n\
+            --batchresponse_1--\r\n";
+
+        let responses = parse_batch("multipart/mixed; boundary=batchresponse_1", body).unwrap();
+        assert_eq!(2, responses.len());
+        assert!(matches!(
+            &responses[0],
+            BatchResponse::Operation(response) if response.status == StatusCode::OK
+        ));
+
+        let BatchResponse::ChangeSet(changeset) = &responses[1] else {
+            panic!("Expected a changeset response");
+        };
+        assert_eq!(2, changeset.len());
+        assert_eq!(StatusCode::CREATED, changeset[1].status);
+        assert_eq!("{\"d\": {\"ID\": \"2\"}}", changeset[1].body);
+    }
+
+    #[test]
+    fn failed_changeset() {
+        let body = "--batchresponse_1\r\n\
+            Content-Type: application/http\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n\
+            HTTP/1.1 400 Bad Request\r\n\
+            X-Request-Id: 3f1c1b7e\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"error\": {\"code\": \"\", \"message\": {\"value\": \"Cost center is blocked\"}}}\r\n\
+            --batchresponse_1--\r\n";
+
+        let mut changeset = ChangeSet::new();
+        changeset.post("https://exact/lines", &[1]).unwrap();
+        changeset.post("https://exact/lines", &[2]).unwrap();
+        let mut batch = Batch::new();
+        batch.changeset(changeset);
+
+        let mut responses = batch
+            .match_responses(
+                parse_batch("multipart/mixed; boundary=batchresponse_1", body).unwrap(),
+            )
+            .unwrap();
+        let Some(BatchResponse::ChangeSet(mut changeset)) = responses.pop() else {
+            panic!("Expected a changeset response");
+        };
+        let response = changeset.remove(0);
+
+        let error = response.error_for_status().unwrap_err();
+        assert!(matches!(
+            error,
+            ExactError::Api { ref message, ref request_id, .. }
+                if message == "Cost center is blocked" && request_id.as_deref() == Some("3f1c1b7e")
+        ));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{ExactClient, ExactError, NoDivisionError};
+use exact_filter::{Filter, FilterOp, Guid};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// The maximum number of codes resolved in one request.
+/// Keeps the URL of a request at a reasonable length.
+const BATCH_SIZE: usize = 20;
+
+/// An entity in Exact identified by a code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeEntity {
+    GLAccount,
+    /// An account, e.g. a customer
+    Account,
+    CostCenter,
+}
+
+impl CodeEntity {
+    fn endpoint(&self) -> &'static str {
+        match self {
+            Self::GLAccount => "/financial/GLAccounts",
+            Self::Account => "/crm/Accounts",
+            Self::CostCenter => "/hrm/Costcenters",
+        }
+    }
+
+    /// The field matched against the code.
+    /// Exact pads account codes with leading spaces, those are matched without them.
+    fn code_field(&self) -> &'static str {
+        match self {
+            Self::Account => "trim(Code)",
+            _ => "Code",
+        }
+    }
+
+    /// The filter matching a code, as shown in errors.
+    fn describe_filter(&self, code: &str) -> String {
+        format!("{} eq '{}'", self.code_field(), code.replace('\'', "''"))
+    }
+
+    /// The code as it is matched.
+    fn normalize(&self, code: &str) -> String {
+        match self {
+            Self::Account => code.trim().to_string(),
+            _ => code.to_string(),
+        }
+    }
+}
+
+impl Display for CodeEntity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GLAccount => write!(f, "GL account"),
+            Self::Account => write!(f, "account"),
+            Self::CostCenter => write!(f, "cost center"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CodeCacheError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid code cache: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// The IDs of entities in Exact by their code.
+///
+/// The cache lives as long as the [ExactClient] it is set on.
+/// Optionally, it is stored in a file, so codes are not resolved again on every run.
+#[derive(Default)]
+pub struct CodeCache {
+    entries: Mutex<HashMap<CacheKey, CachedId>>,
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    division: i32,
+    entity: CodeEntity,
+    code: String,
+}
+
+#[derive(Debug, Clone)]
+struct CachedId {
+    id: Guid,
+    resolved_at: OffsetDateTime,
+}
+
+/// An entry of the cache file.
+#[derive(Serialize, Deserialize)]
+struct CacheFileEntry {
+    division: i32,
+    entity: CodeEntity,
+    code: String,
+    id: Guid,
+    #[serde(with = "time::serde::rfc3339")]
+    resolved_at: OffsetDateTime,
+}
+
+impl CodeCache {
+    /// A cache that is kept in memory only.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Load the cache from `file`. The file need not exist yet.
+    /// Entries resolved longer than `ttl` ago are dropped.
+    ///
+    /// # Errors
+    ///
+    /// If the file exists, but could not be read or parsed
+    pub fn load<P: AsRef<Path>>(file: P, ttl: Duration) -> Result<Self, CodeCacheError> {
+        let file = file.as_ref().to_path_buf();
+        if !file.exists() {
+            return Ok(Self {
+                entries: Mutex::default(),
+                file: Some(file),
+            });
+        }
+
+        let entries: Vec<CacheFileEntry> = serde_json::from_slice(&std::fs::read(&file)?)?;
+        let now = OffsetDateTime::now_utc();
+        let entries = entries
+            .into_iter()
+            .filter(|entry| now - entry.resolved_at < ttl)
+            .map(|entry| {
+                (
+                    CacheKey {
+                        division: entry.division,
+                        entity: entry.entity,
+                        code: entry.code,
+                    },
+                    CachedId {
+                        id: entry.id,
+                        resolved_at: entry.resolved_at,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            entries: Mutex::new(entries),
+            file: Some(file),
+        })
+    }
+
+    /// Write the cache to its file.
+    /// Does nothing if the cache is kept in memory only.
+    ///
+    /// # Errors
+    ///
+    /// If the file could not be written
+    pub fn save(&self) -> Result<(), CodeCacheError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let mut entries = self
+            .lock()
+            .iter()
+            .map(|(key, cached)| CacheFileEntry {
+                division: key.division,
+                entity: key.entity,
+                code: key.code.clone(),
+                id: cached.id.clone(),
+                resolved_at: cached.resolved_at,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| (a.division, &a.code).cmp(&(b.division, &b.code)));
+
+        std::fs::write(file, serde_json::to_vec_pretty(&entries)?)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Guid> {
+        self.lock().get(key).map(|cached| cached.id.clone())
+    }
+
+    fn insert(&self, key: CacheKey, id: Guid) {
+        self.lock().insert(
+            key,
+            CachedId {
+                id,
+                resolved_at: OffsetDateTime::now_utc(),
+            },
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CacheKey, CachedId>> {
+        // The map is never left in an inconsistent state, so a poisoned lock can be used
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ExactClient {
+    /// Get the IDs of entities by their codes.
+    /// Codes not in the cache are resolved with one request per [BATCH_SIZE] codes.
+    /// Codes that do not exist in Exact are absent from the result.
+    ///
+    /// # Errors
+    ///
+    /// - If a request fails
+    /// - If more than one entity has the same code
+    pub async fn resolve_codes<S: AsRef<str>>(
+        &self,
+        entity: CodeEntity,
+        codes: &[S],
+    ) -> Result<HashMap<String, Guid>, ExactError> {
+        let division = self.accounting_division.ok_or(NoDivisionError)?;
+        let key = |code: &str| CacheKey {
+            division,
+            entity,
+            code: entity.normalize(code),
+        };
+
+        let mut resolved = HashMap::new();
+        let mut unresolved = Vec::new();
+        for code in codes {
+            let code = entity.normalize(code.as_ref());
+            match self.code_cache.get(&key(&code)) {
+                Some(id) => {
+                    resolved.insert(code, id);
+                }
+                None if !unresolved.contains(&code) => unresolved.push(code),
+                None => {}
+            }
+        }
+
+        for batch in unresolved.chunks(BATCH_SIZE) {
+            debug!(
+                "Resolving {} {entity} codes: {}",
+                batch.len(),
+                batch.join(", ")
+            );
+
+            #[derive(Deserialize)]
+            struct Response {
+                #[serde(rename = "ID")]
+                id: Guid,
+                #[serde(rename = "Code")]
+                code: String,
+            }
+
+            let responses: Vec<Response> = self
+                .list_paginated(self.divisioned_url(format!(
+                    "{}?$filter={}&$select=ID,Code",
+                    entity.endpoint(),
+                    code_filter(entity, batch).finalize()
+                ))?)
+                .await?;
+
+            let mut ids = HashMap::<String, Vec<Guid>>::new();
+            for response in responses {
+                ids.entry(entity.normalize(&response.code))
+                    .or_default()
+                    .push(response.id);
+            }
+
+            for (code, mut ids) in ids {
+                if ids.len() > 1 {
+                    return Err(ExactError::Ambiguous {
+                        entity: entity.to_string(),
+                        filter: entity.describe_filter(&code),
+                        count: ids.len(),
+                    });
+                }
+
+                let id = ids.remove(0);
+                self.code_cache.insert(key(&code), id.clone());
+                resolved.insert(code, id);
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Get the ID of an entity by its code.
+    ///
+    /// # Errors
+    ///
+    /// - If the request fails
+    /// - If no entity, or more than one entity, with the code exists
+    pub async fn resolve_code<S: AsRef<str>>(
+        &self,
+        entity: CodeEntity,
+        code: S,
+    ) -> Result<Guid, ExactError> {
+        let code = entity.normalize(code.as_ref());
+        self.resolve_codes(entity, &[&code])
+            .await?
+            .remove(&code)
+            .ok_or(ExactError::NotFound {
+                entity: entity.to_string(),
+                filter: entity.describe_filter(&code),
+            })
+    }
+
+    /// Set the cache used to resolve codes.
+    pub fn set_code_cache(&mut self, code_cache: CodeCache) {
+        self.code_cache = code_cache;
+    }
+
+    /// The cache used to resolve codes.
+    pub fn code_cache(&self) -> &CodeCache {
+        &self.code_cache
+    }
+}
+
+/// A filter matching any of the codes.
+fn code_filter<S: AsRef<str>>(entity: CodeEntity, codes: &[S]) -> Filter {
+    let field = entity.code_field();
+    let mut codes = codes.iter().map(AsRef::as_ref);
+    let first = codes.next().unwrap_or_default();
+
+    codes.fold(
+        Filter::new(field, first, FilterOp::Equals),
+        |filter, code| filter.or(field, code, FilterOp::Equals),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::datetime;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{name}-{}.json", std::process::id()))
+    }
+
+    #[test]
+    fn filter_on_any_code() {
+        assert_eq!(
+            "Code+eq+'8000'+or+Code+eq+'8010'",
+            code_filter(CodeEntity::GLAccount, &["8000", "8010"]).finalize()
+        );
+        assert_eq!(
+            "trim(Code)+eq+'1001'",
+            code_filter(CodeEntity::Account, &["1001"]).finalize()
+        );
+    }
+
+    #[test]
+    fn quotes_in_codes_are_escaped() {
+        assert_eq!(
+            "trim(Code)+eq+'O''Neill'",
+            code_filter(CodeEntity::Account, &["O'Neill"]).finalize()
+        );
+        assert_eq!(
+            "trim(Code) eq 'O''Neill'",
+            CodeEntity::Account.describe_filter("O'Neill")
+        );
+    }
+
+    #[test]
+    fn expired_entries_are_dropped() {
+        let file = temp_file("code-cache-ttl");
+        let entries = [
+            CacheFileEntry {
+                division: 1,
+                entity: CodeEntity::GLAccount,
+                code: "8000".to_string(),
+                id: Guid::new("00000000-0000-0000-0000-000000000001"),
+                resolved_at: OffsetDateTime::now_utc(),
+            },
+            CacheFileEntry {
+                division: 1,
+                entity: CodeEntity::GLAccount,
+                code: "8010".to_string(),
+                id: Guid::new("00000000-0000-0000-0000-000000000002"),
+                resolved_at: datetime!(2024-01-01 12:00 UTC),
+            },
+        ];
+        std::fs::write(&file, serde_json::to_vec(&entries).unwrap()).unwrap();
+
+        let cache = CodeCache::load(&file, Duration::from_secs(24 * 60 * 60)).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let key = |code: &str| CacheKey {
+            division: 1,
+            entity: CodeEntity::GLAccount,
+            code: code.to_string(),
+        };
+        assert_eq!(
+            Some(Guid::new("00000000-0000-0000-0000-000000000001")),
+            cache.get(&key("8000"))
+        );
+        assert_eq!(None, cache.get(&key("8010")));
+    }
+
+    #[test]
+    fn saved_and_loaded() {
+        let file = temp_file("code-cache-save");
+        let cache = CodeCache::load(&file, Duration::from_secs(60)).unwrap();
+        let key = CacheKey {
+            division: 2,
+            entity: CodeEntity::Account,
+            code: "1001".to_string(),
+        };
+        cache.insert(
+            key.clone(),
+            Guid::new("00000000-0000-0000-0000-000000000003"),
+        );
+        cache.save().unwrap();
+
+        let loaded = CodeCache::load(&file, Duration::from_secs(60)).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(
+            Some(Guid::new("00000000-0000-0000-0000-000000000003")),
+            loaded.get(&key)
+        );
+    }
+}
@@ -1,12 +1,19 @@
 use log::trace;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::Client;
+use reqwest::{Client, Response, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use std::ops::Deref;
 use thiserror::Error;
 
 pub mod api;
+pub mod batch;
+pub mod cache;
+pub mod region;
+pub mod sync;
+
+use cache::CodeCache;
+pub use region::ExactRegion;
 
 #[derive(Debug, Error)]
 pub enum ExactError {
@@ -14,11 +21,110 @@ pub enum ExactError {
     Request(#[from] reqwest::Error),
     #[error("{0}")]
     NoAccountingDivision(#[from] NoDivisionError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid batch response from Exact: {0}")]
+    InvalidBatch(String),
+    #[error("No {entity} found in Exact matching {filter}")]
+    NotFound { entity: String, filter: String },
+    #[error("Expected one {entity} in Exact matching {filter}, found {count}")]
+    Ambiguous {
+        entity: String,
+        filter: String,
+        count: usize,
+    },
+    /// Exact responded with an error.
+    #[error(
+        "Exact returned HTTP {status}: {message} (error code: {}, request ID: {})",
+        .code.as_deref().unwrap_or("none"),
+        .request_id.as_deref().unwrap_or("unknown")
+    )]
+    Api {
+        status: StatusCode,
+        /// The OData error code. Often absent
+        code: Option<String>,
+        message: String,
+        /// The `X-Request-Id` of the response, to report the failure to Exact support
+        request_id: Option<String>,
+    },
+}
+
+impl ExactError {
+    /// The HTTP status of the failed request, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Request(e) => e.status(),
+            Self::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// The error for a response with an error status.
+    /// The message is taken from the OData error in `body`, or else the body itself.
+    fn from_body(status: StatusCode, request_id: Option<String>, body: &str) -> Self {
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            error: ODataError,
+        }
+
+        #[derive(Deserialize)]
+        struct ODataError {
+            code: Option<String>,
+            message: ODataMessage,
+        }
+
+        #[derive(Deserialize)]
+        struct ODataMessage {
+            value: String,
+        }
+
+        let (code, message) = match serde_json::from_str::<ErrorBody>(body) {
+            Ok(body) => (body.error.code, body.error.message.value),
+            Err(_) if !body.trim().is_empty() => (None, body.trim().to_string()),
+            Err(_) => (
+                None,
+                status.canonical_reason().unwrap_or_default().to_string(),
+            ),
+        };
+
+        Self::Api {
+            status,
+            code: code.filter(|code| !code.is_empty()),
+            message,
+            request_id,
+        }
+    }
+}
+
+/// Checks the status of a response from Exact.
+pub(crate) trait ExactResponse: Sized {
+    /// Turn a response with an error status into [ExactError::Api].
+    async fn exact_error_for_status(self) -> Result<Self, ExactError>;
+}
+
+impl ExactResponse for Response {
+    async fn exact_error_for_status(self) -> Result<Self, ExactError> {
+        let status = self.status();
+        if !(status.is_client_error() || status.is_server_error()) {
+            return Ok(self);
+        }
+
+        let request_id = self
+            .headers()
+            .get("X-Request-Id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = self.text().await?;
+
+        Err(ExactError::from_body(status, request_id, &body))
+    }
 }
 
 pub struct ExactClient {
     client: Client,
+    region: ExactRegion,
     accounting_division: Option<i32>,
+    code_cache: CodeCache,
 }
 
 #[derive(Debug, Error)]
@@ -26,7 +132,7 @@ pub struct ExactClient {
 pub struct NoDivisionError;
 
 impl ExactClient {
-    pub fn new<S: AsRef<str>>(access_token: S) -> Self {
+    pub fn new<S: AsRef<str>>(access_token: S, region: ExactRegion) -> Self {
         let mut hm = HeaderMap::new();
         hm.insert(
             "Authorization",
@@ -46,17 +152,19 @@ impl ExactClient {
 
         Self {
             client,
+            region,
             accounting_division: None,
+            code_cache: CodeCache::in_memory(),
         }
     }
 
-    /// Format a URL for Exact Online.
+    /// Format a URL for the Exact Online environment of the client.
     /// If the Exact documentation specifies `/api/v1/current/Me`, pass that to this function.
     /// You should only use this for endpoints that do not require the accounting division. The accounting
     /// division is the number after `/api/v1/`. For example, in `/api/v1/55861/salesentry/SalesEntries`
     /// the accounting division is `55861`. For endpoints with an accounting division, use [Self::divisioned_url].
-    pub fn url<S: AsRef<str>>(s: S) -> String {
-        format!("https://start.exactonline.nl{}", s.as_ref())
+    pub fn url<S: AsRef<str>>(&self, s: S) -> String {
+        self.region.url(s)
     }
 
     /// Format a URL within the accounting division namespace.
@@ -68,14 +176,43 @@ impl ExactClient {
     /// If no accounting division is set. To set the accounting division, use [Self::set_division]
     pub fn divisioned_url<S: AsRef<str>>(&self, s: S) -> Result<String, NoDivisionError> {
         let div = self.accounting_division.ok_or(NoDivisionError)?;
-        let url = Self::url(format!("/api/v1/{div}{}", s.as_ref()));
+        let url = self.url(format!("/api/v1/{div}{}", s.as_ref()));
 
         trace!("URL: {url}");
 
         Ok(url)
     }
 
-    /// Set the accounting division ID. This ID can be obtained with [api::me::accounting_division].
+    /// List all values from an endpoint that is paginated.
+    /// Exact returns at most 60 values per page, or 1000 for the `/bulk/` endpoints.
+    /// Pass the URL as formatted by [Self::divisioned_url].
+    pub async fn list_paginated<S: AsRef<str>, T: DeserializeOwned>(
+        &self,
+        url: S,
+    ) -> Result<Vec<T>, ExactError> {
+        let mut data = Vec::new();
+        let mut next = Some(url.as_ref().to_string());
+
+        while let Some(next_url) = next {
+            let response: ExactPayload<T> = self
+                .get(next_url)
+                .send()
+                .await?
+                .exact_error_for_status()
+                .await?
+                .json()
+                .await?;
+
+            next = response.d.next.clone();
+            data.append(&mut response.values());
+        }
+
+        Ok(data)
+    }
+
+    /// Set the accounting division ID.
+    /// The division last opened by the user can be obtained with [api::me::accounting_division],
+    /// all accessible divisions with [api::division::list_divisions].
     pub fn set_division(&mut self, accounting_division: i32) {
         self.accounting_division = Some(accounting_division);
     }
@@ -97,6 +234,9 @@ pub struct ExactPayload<T> {
 #[derive(Deserialize)]
 pub struct ExactData<T> {
     results: Vec<ExactResult<T>>,
+    /// The URL of the next page, if there is one
+    #[serde(rename = "__next")]
+    next: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -105,9 +245,40 @@ pub struct ExactResult<T> {
     value: T,
 }
 
-impl<T: DeserializeOwned> ExactPayload<T> {
+/// Payload returned by Exact after creating an entity.
+#[derive(Deserialize)]
+pub struct ExactCreated<T> {
+    d: T,
+}
+
+impl<T: DeserializeOwned> ExactCreated<T> {
     pub fn value(self) -> T {
-        self.d.results.into_iter().nth(0).unwrap().value
+        self.d
+    }
+}
+
+impl<T: DeserializeOwned> ExactPayload<T> {
+    /// The single value of the response.
+    /// `entity` and `filter` describe what was requested, for the error.
+    ///
+    /// # Errors
+    ///
+    /// If the response contains no values, or more than one
+    pub fn value<S: AsRef<str>>(self, entity: &str, filter: S) -> Result<T, ExactError> {
+        let count = self.d.results.len();
+        let mut results = self.d.results.into_iter();
+        match (results.next(), count) {
+            (Some(result), 1) => Ok(result.value),
+            (None, _) => Err(ExactError::NotFound {
+                entity: entity.to_string(),
+                filter: filter.as_ref().to_string(),
+            }),
+            (Some(_), count) => Err(ExactError::Ambiguous {
+                entity: entity.to_string(),
+                filter: filter.as_ref().to_string(),
+                count,
+            }),
+        }
     }
 
     pub fn values(self) -> Vec<T> {
@@ -118,3 +289,86 @@ impl<T: DeserializeOwned> ExactPayload<T> {
             .collect::<Vec<_>>()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Response {
+        #[serde(rename = "ID")]
+        id: i32,
+    }
+
+    fn payload(ids: &[i32]) -> ExactPayload<Response> {
+        let results = ids
+            .iter()
+            .map(|id| format!(r#"{{"ID": {id}}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        serde_json::from_str(&format!(r#"{{"d": {{"results": [{results}]}}}}"#)).unwrap()
+    }
+
+    #[test]
+    fn single_value() {
+        let value = payload(&[1])
+            .value("sales entry", "EntryNumber eq 1")
+            .unwrap();
+        assert_eq!(1, value.id);
+    }
+
+    #[test]
+    fn no_value() {
+        let error = payload(&[])
+            .value("sales entry", "EntryNumber eq 1")
+            .unwrap_err();
+        assert!(matches!(error, ExactError::NotFound { .. }));
+        assert_eq!(
+            "No sales entry found in Exact matching EntryNumber eq 1",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn odata_error() {
+        let error = ExactError::from_body(
+            StatusCode::BAD_REQUEST,
+            Some("3f1c1b7e".to_string()),
+            r#"{"error": {"code": "", "message": {"lang": "", "value": "Period is closed"}}}"#,
+        );
+
+        assert_eq!(Some(StatusCode::BAD_REQUEST), error.status());
+        assert_eq!(
+            "Exact returned HTTP 400 Bad Request: Period is closed (error code: none, request ID: 3f1c1b7e)",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn plain_error() {
+        let error = ExactError::from_body(StatusCode::UNAUTHORIZED, None, "");
+        assert!(matches!(
+            error,
+            ExactError::Api { ref message, .. } if message == "Unauthorized"
+        ));
+
+        let error =
+            ExactError::from_body(StatusCode::BAD_GATEWAY, None, "<html>Bad gateway</html>");
+        assert!(matches!(
+            error,
+            ExactError::Api { ref message, .. } if message == "<html>Bad gateway</html>"
+        ));
+    }
+
+    #[test]
+    fn multiple_values() {
+        let error = payload(&[1, 2])
+            .value("account", "trim(Code) eq '1001'")
+            .unwrap_err();
+        assert!(matches!(error, ExactError::Ambiguous { count: 2, .. }));
+        assert_eq!(
+            "Expected one account in Exact matching trim(Code) eq '1001', found 2",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// The Exact Online environment to connect to.
+/// Every country Exact operates in has its own environment, with its own accounts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExactRegion {
+    #[default]
+    Netherlands,
+    Belgium,
+    UnitedKingdom,
+    Germany,
+    Spain,
+    France,
+    UnitedStates,
+    /// Any other base URL, e.g. of a mock server.
+    /// Should *not* end with a slash (`/`).
+    Custom(String),
+}
+
+impl ExactRegion {
+    /// The base URL of the environment, without a trailing slash.
+    pub fn base_url(&self) -> &str {
+        match self {
+            Self::Netherlands => "https://start.exactonline.nl",
+            Self::Belgium => "https://start.exactonline.be",
+            Self::UnitedKingdom => "https://start.exactonline.co.uk",
+            Self::Germany => "https://start.exactonline.de",
+            Self::Spain => "https://start.exactonline.es",
+            Self::France => "https://start.exactonline.fr",
+            Self::UnitedStates => "https://start.exactonline.com",
+            Self::Custom(url) => url.trim_end_matches('/'),
+        }
+    }
+
+    /// Format a URL in the environment.
+    pub fn url<S: AsRef<str>>(&self, path: S) -> String {
+        format!("{}{}", self.base_url(), path.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExactRegion;
+
+    #[test]
+    fn custom_url() {
+        let region: ExactRegion =
+            serde_json::from_str(r#"{"custom": "http://localhost:8080/"}"#).unwrap();
+        assert_eq!(
+            "http://localhost:8080/api/oauth2/token",
+            region.url("/api/oauth2/token")
+        );
+    }
+
+    #[test]
+    fn named_region() {
+        let region: ExactRegion = serde_json::from_str(r#""united_kingdom""#).unwrap();
+        assert_eq!("https://start.exactonline.co.uk", region.base_url());
+    }
+}
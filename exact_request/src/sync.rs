@@ -0,0 +1,271 @@
+use crate::{ExactClient, ExactError, ExactPayload, ExactResponse, NoDivisionError};
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The maximum number of records returned by one request to a sync endpoint.
+const SYNC_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Error)]
+pub enum SyncCursorError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid sync cursor: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// How far each sync endpoint has been read, per division.
+///
+/// Exact gives every record a `Timestamp`, which increases whenever the record changes.
+/// The cursor is the highest timestamp read, the next sync only returns records changed after it.
+/// Optionally, it is stored in a file, so a local mirror can be kept up to date between runs.
+///
+/// The CLI does not sync yet, so there is no configuration for the cursor file:
+/// a caller of this library chooses the file with [SyncCursor::load] and calls [SyncCursor::save]
+/// once the synced records are stored.
+#[derive(Debug, Default)]
+pub struct SyncCursor {
+    timestamps: HashMap<CursorKey, i64>,
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CursorKey {
+    division: i32,
+    endpoint: String,
+}
+
+/// An entry of the cursor file.
+#[derive(Serialize, Deserialize)]
+struct CursorFileEntry {
+    division: i32,
+    endpoint: String,
+    timestamp: i64,
+}
+
+/// A record returned by a sync endpoint.
+#[derive(Deserialize)]
+struct Synced<T> {
+    #[serde(rename = "Timestamp", deserialize_with = "as_i64")]
+    timestamp: i64,
+    #[serde(flatten)]
+    value: T,
+}
+
+impl SyncCursor {
+    /// A cursor that is kept in memory only.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Load the cursor from `file`. The file need not exist yet,
+    /// in which case every endpoint is synced from the start.
+    ///
+    /// # Errors
+    ///
+    /// If the file exists, but could not be read or parsed
+    pub fn load<P: AsRef<Path>>(file: P) -> Result<Self, SyncCursorError> {
+        let file = file.as_ref().to_path_buf();
+        if !file.exists() {
+            return Ok(Self {
+                timestamps: HashMap::new(),
+                file: Some(file),
+            });
+        }
+
+        let entries: Vec<CursorFileEntry> = serde_json::from_slice(&std::fs::read(&file)?)?;
+        let timestamps = entries
+            .into_iter()
+            .map(|entry| {
+                (
+                    CursorKey {
+                        division: entry.division,
+                        endpoint: entry.endpoint,
+                    },
+                    entry.timestamp,
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            timestamps,
+            file: Some(file),
+        })
+    }
+
+    /// Write the cursor to its file.
+    /// Does nothing if the cursor is kept in memory only.
+    ///
+    /// # Errors
+    ///
+    /// If the file could not be written
+    pub fn save(&self) -> Result<(), SyncCursorError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let mut entries = self
+            .timestamps
+            .iter()
+            .map(|(key, timestamp)| CursorFileEntry {
+                division: key.division,
+                endpoint: key.endpoint.clone(),
+                timestamp: *timestamp,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| (a.division, &a.endpoint).cmp(&(b.division, &b.endpoint)));
+
+        std::fs::write(file, serde_json::to_vec_pretty(&entries)?)?;
+        Ok(())
+    }
+
+    /// The highest timestamp read from `endpoint`, or `None` if it was never synced.
+    pub fn timestamp(&self, division: i32, endpoint: &str) -> Option<i64> {
+        self.timestamps
+            .get(&CursorKey {
+                division,
+                endpoint: endpoint.to_string(),
+            })
+            .copied()
+    }
+
+    /// Forget the position of `endpoint`, so the next sync reads all records again.
+    pub fn reset(&mut self, division: i32, endpoint: &str) {
+        self.timestamps.remove(&CursorKey {
+            division,
+            endpoint: endpoint.to_string(),
+        });
+    }
+
+    fn advance(&mut self, division: i32, endpoint: &str, timestamp: i64) {
+        let current = self
+            .timestamps
+            .entry(CursorKey {
+                division,
+                endpoint: endpoint.to_string(),
+            })
+            .or_insert(timestamp);
+        *current = timestamp.max(*current);
+    }
+}
+
+impl ExactClient {
+    /// Read all records of a sync endpoint changed since the last sync, e.g. `Financial/TransactionLines`
+    /// for `/sync/Financial/TransactionLines`. `select` lists the fields to return, the `Timestamp` is added.
+    /// The cursor is only advanced if all records were read.
+    ///
+    /// Deleted records are not returned, those are listed by the `Deleted` sync endpoint.
+    ///
+    /// # Errors
+    ///
+    /// If a request fails
+    pub async fn sync<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        select: &str,
+        cursor: &mut SyncCursor,
+    ) -> Result<Vec<T>, ExactError> {
+        let division = self.accounting_division.ok_or(NoDivisionError)?;
+        let mut timestamp = cursor.timestamp(division, endpoint).unwrap_or(1);
+        let mut data = Vec::new();
+
+        loop {
+            debug!("Syncing {endpoint} from timestamp {timestamp}");
+            let response: ExactPayload<Synced<T>> = self
+                .get(self.divisioned_url(sync_path(endpoint, select, timestamp))?)
+                .send()
+                .await?
+                .exact_error_for_status()
+                .await?
+                .json()
+                .await?;
+
+            let records = response.values();
+            let count = records.len();
+            for record in records {
+                timestamp = timestamp.max(record.timestamp);
+                data.push(record.value);
+            }
+
+            if count < SYNC_PAGE_SIZE {
+                break;
+            }
+        }
+
+        cursor.advance(division, endpoint, timestamp);
+        Ok(data)
+    }
+}
+
+/// The path of one page of a sync endpoint, starting after `timestamp`.
+fn sync_path(endpoint: &str, select: &str, timestamp: i64) -> String {
+    format!("/sync/{endpoint}?$filter=Timestamp+gt+{timestamp}L&$select=Timestamp,{select}")
+}
+
+/// Exact serializes 64-bit integers as strings, accept both.
+fn as_i64<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(i64),
+        String(String),
+    }
+
+    match Value::deserialize(deserializer)? {
+        Value::Number(value) => Ok(value),
+        Value::String(value) => value.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sync_page_path() {
+        assert_eq!(
+            "/sync/Financial/TransactionLines?$filter=Timestamp+gt+42L&$select=Timestamp,ID,AmountDC",
+            sync_path("Financial/TransactionLines", "ID,AmountDC", 42)
+        );
+    }
+
+    #[test]
+    fn timestamp_as_number_or_string() {
+        #[derive(Deserialize)]
+        struct Line {
+            #[serde(rename = "ID")]
+            id: i32,
+        }
+
+        let payload: ExactPayload<Synced<Line>> = serde_json::from_str(
+            r#"{"d": {"results": [{"Timestamp": 10, "ID": 1}, {"Timestamp": "11", "ID": 2}]}}"#,
+        )
+        .unwrap();
+        let records = payload.values();
+
+        assert_eq!(10, records[0].timestamp);
+        assert_eq!(11, records[1].timestamp);
+        assert_eq!(2, records[1].value.id);
+    }
+
+    #[test]
+    fn cursor_saved_and_loaded() {
+        let file = std::env::temp_dir().join(format!("sync-cursor-{}.json", std::process::id()));
+        let mut cursor = SyncCursor::load(&file).unwrap();
+        cursor.advance(1, "Financial/TransactionLines", 20);
+        cursor.advance(1, "Financial/TransactionLines", 10);
+        cursor.save().unwrap();
+
+        let loaded = SyncCursor::load(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(Some(20), loaded.timestamp(1, "Financial/TransactionLines"));
+        assert_eq!(None, loaded.timestamp(2, "Financial/TransactionLines"));
+    }
+}
@@ -0,0 +1,137 @@
+use exact_filter::Guid;
+use exact_request::api::sales_entry::{create_sales_entry_lines, NewSalesEntryLine};
+use exact_request::{ExactClient, ExactError, ExactRegion};
+use reqwest::StatusCode;
+use wiremock::matchers::{body_string_contains, header_regex, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client(server: &MockServer) -> ExactClient {
+    let mut client = ExactClient::new("token", ExactRegion::Custom(server.uri()));
+    client.set_division(1);
+    client
+}
+
+fn line(description: &str) -> NewSalesEntryLine {
+    NewSalesEntryLine {
+        entry_id: Some(Guid::new("2d8d6cbc-d2b8-4d0b-8e45-3c0a64c63a1f")),
+        gl_account: Guid::new("8a0a4a24-04a3-4a77-9c47-3c7e7ab5b8f0"),
+        amount_fc: 10.0,
+        vat_code: Some("2".to_string()),
+        vat_amount_fc: Some(2.1),
+        cost_center: None,
+        cost_unit: None,
+        description: description.to_string(),
+    }
+}
+
+/// A batch response with one part, of the given content type.
+fn batch_response(part_content_type: &str, part: &str) -> ResponseTemplate {
+    let body = format!(
+        "--batchresponse_1\r\n\
+        Content-Type: {part_content_type}\r\n\r\n\
+        {part}\
+        --batchresponse_1--\r\n"
+    );
+    ResponseTemplate::new(202).set_body_raw(body, "multipart/mixed; boundary=batchresponse_1")
+}
+
+fn created(id: &str) -> String {
+    format!(
+        "--changesetresponse_1\r\n\
+        Content-Type: application/http\r\n\
+        Content-Transfer-Encoding: binary\r\n\r\n\
+        HTTP/1.1 201 Created\r\n\
+        Content-Type: application/json\r\n\r\n\
+        {{\"d\": {{\"ID\": \"{id}\"}}}}\r\n"
+    )
+}
+
+#[tokio::test]
+async fn lines_added_in_one_changeset() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/1/$batch"))
+        .and(header_regex(
+            "Content-Type",
+            "^multipart/mixed; boundary=batch_",
+        ))
+        .and(body_string_contains(format!(
+            "POST {}/api/v1/1/salesentry/SalesEntryLines HTTP/1.1",
+            server.uri()
+        )))
+        .and(body_string_contains("\"Description\":\"Ticket\""))
+        .and(body_string_contains("\"Description\":\"Merch\""))
+        .respond_with(batch_response(
+            "multipart/mixed; boundary=changesetresponse_1",
+            &format!(
+                "{}{}--changesetresponse_1--\r\n",
+                created("c4a6b5b2-1f4e-4d7a-9d0e-0a1b2c3d4e5f"),
+                created("5e4d3c2b-1a0b-4e9d-8c7b-6a5f4e3d2c1b"),
+            ),
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let ids = create_sales_entry_lines(&client(&server), &[line("Ticket"), line("Merch")])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        vec![
+            Guid::new("c4a6b5b2-1f4e-4d7a-9d0e-0a1b2c3d4e5f"),
+            Guid::new("5e4d3c2b-1a0b-4e9d-8c7b-6a5f4e3d2c1b"),
+        ],
+        ids
+    );
+}
+
+#[tokio::test]
+async fn failed_changeset() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/1/$batch"))
+        .respond_with(batch_response(
+            "application/http\r\nContent-Transfer-Encoding: binary",
+            "HTTP/1.1 400 Bad Request\r\n\
+            X-Request-Id: 7c1d\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"error\": {\"code\": \"\", \"message\": {\"lang\": \"\", \"value\": \"GL account is blocked\"}}}\r\n",
+        ))
+        .mount(&server)
+        .await;
+
+    let error = create_sales_entry_lines(&client(&server), &[line("Ticket"), line("Merch")])
+        .await
+        .unwrap_err();
+
+    match error {
+        ExactError::Api {
+            status,
+            message,
+            request_id,
+            ..
+        } => {
+            assert_eq!(StatusCode::BAD_REQUEST, status);
+            assert_eq!("GL account is blocked", message);
+            assert_eq!(Some("7c1d"), request_id.as_deref());
+        }
+        e => panic!("Expected an API error, got {e}"),
+    }
+}
+
+#[tokio::test]
+async fn batch_request_rejected() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/1/$batch"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    let error = create_sales_entry_lines(&client(&server), &[line("Ticket")])
+        .await
+        .unwrap_err();
+
+    assert_eq!(Some(StatusCode::UNAUTHORIZED), error.status());
+}
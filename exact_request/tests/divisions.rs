@@ -0,0 +1,29 @@
+use exact_request::api::division::list_divisions;
+use exact_request::{ExactClient, ExactRegion};
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn divisions_of_all_licenses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/1/system/AllDivisions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "d": { "results": [
+                { "Code": 1, "Description": "Vereniging", "Main": true, "BlockingStatus": 0 },
+                { "Code": 2, "Description": "Oud", "Main": false, "BlockingStatus": 100 }
+            ] }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut client = ExactClient::new("token", ExactRegion::Custom(server.uri()));
+    client.set_division(1);
+    let divisions = list_divisions(&client).await.unwrap();
+
+    assert_eq!(2, divisions.len());
+    assert!(!divisions[0].is_blocked());
+    assert!(divisions[1].is_blocked());
+}
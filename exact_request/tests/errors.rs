@@ -0,0 +1,96 @@
+use exact_request::api::me::accounting_division;
+use exact_request::{ExactClient, ExactError, ExactRegion};
+use reqwest::StatusCode;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client(server: &MockServer) -> ExactClient {
+    ExactClient::new("token", ExactRegion::Custom(server.uri()))
+}
+
+async fn mock_me(server: &MockServer, response: ResponseTemplate) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1/current/Me"))
+        .respond_with(response)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn success_passes_through() {
+    let server = MockServer::start().await;
+    mock_me(
+        &server,
+        ResponseTemplate::new(200).set_body_json(json!({
+            "d": { "results": [{ "AccountingDivision": 55861 }] }
+        })),
+    )
+    .await;
+
+    assert_eq!(55861, accounting_division(&client(&server)).await.unwrap());
+}
+
+#[tokio::test]
+async fn odata_error() {
+    let server = MockServer::start().await;
+    mock_me(
+        &server,
+        ResponseTemplate::new(403)
+            .insert_header("X-Request-Id", "3f2a8c1e")
+            .set_body_json(json!({
+                "error": {
+                    "code": "",
+                    "message": { "lang": "", "value": "Forbidden: no access to division 55861" }
+                }
+            })),
+    )
+    .await;
+
+    match accounting_division(&client(&server)).await.unwrap_err() {
+        ExactError::Api {
+            status,
+            code,
+            message,
+            request_id,
+        } => {
+            assert_eq!(StatusCode::FORBIDDEN, status);
+            assert_eq!(None, code);
+            assert_eq!("Forbidden: no access to division 55861", message);
+            assert_eq!(Some("3f2a8c1e"), request_id.as_deref());
+        }
+        e => panic!("Expected an API error, got {e}"),
+    }
+}
+
+#[tokio::test]
+async fn plain_error_body() {
+    let server = MockServer::start().await;
+    mock_me(
+        &server,
+        ResponseTemplate::new(503).set_body_string("Service unavailable, try again later\r\n"),
+    )
+    .await;
+
+    let error = accounting_division(&client(&server)).await.unwrap_err();
+
+    assert_eq!(Some(StatusCode::SERVICE_UNAVAILABLE), error.status());
+    assert!(matches!(
+        error,
+        ExactError::Api { message, request_id: None, .. }
+            if message == "Service unavailable, try again later"
+    ));
+}
+
+#[tokio::test]
+async fn empty_error_body() {
+    let server = MockServer::start().await;
+    mock_me(&server, ResponseTemplate::new(401)).await;
+
+    let error = accounting_division(&client(&server)).await.unwrap_err();
+
+    assert!(matches!(
+        error,
+        ExactError::Api { status: StatusCode::UNAUTHORIZED, message, .. } if message == "Unauthorized"
+    ));
+}
@@ -0,0 +1,110 @@
+use exact_request::api::transaction_line::sync_transaction_lines;
+use exact_request::sync::SyncCursor;
+use exact_request::{ExactClient, ExactRegion};
+use serde_json::{json, Value};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ENDPOINT: &str = "Financial/TransactionLines";
+
+fn client(server: &MockServer) -> ExactClient {
+    let mut client = ExactClient::new("token", ExactRegion::Custom(server.uri()));
+    client.set_division(1);
+    client
+}
+
+fn transaction_line(timestamp: i64) -> Value {
+    json!({
+        "Timestamp": timestamp.to_string(),
+        "ID": format!("00000000-0000-0000-0000-{timestamp:012}"),
+        "EntryNumber": 24000001,
+        "JournalCode": "70",
+        "GLAccountCode": "1302",
+        "CostCenter": null,
+        "Description": "Mollie",
+        "AmountDC": 12.5,
+        "FinancialYear": 2024,
+        "FinancialPeriod": 10
+    })
+}
+
+/// Serve the lines with a timestamp above `after`.
+async fn mock_page(server: &MockServer, after: i64, timestamps: impl Iterator<Item = i64>) {
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v1/1/sync/{ENDPOINT}")))
+        .and(query_param("$filter", format!("Timestamp gt {after}L")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "d": { "results": timestamps.map(transaction_line).collect::<Vec<_>>() }
+        })))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn pages_until_a_short_page() {
+    let server = MockServer::start().await;
+    // A full page of 1000 records means there may be more
+    mock_page(&server, 1, 2..=1001).await;
+    mock_page(&server, 1001, 1002..=1003).await;
+
+    let mut cursor = SyncCursor::in_memory();
+    let lines = sync_transaction_lines(&client(&server), &mut cursor)
+        .await
+        .unwrap();
+
+    assert_eq!(1002, lines.len());
+    assert_eq!(Some(1003), cursor.timestamp(1, ENDPOINT));
+}
+
+#[tokio::test]
+async fn continues_from_the_cursor() {
+    let server = MockServer::start().await;
+    mock_page(&server, 1, 2..=3).await;
+    mock_page(&server, 3, std::iter::empty()).await;
+
+    let client = client(&server);
+    let mut cursor = SyncCursor::in_memory();
+    assert_eq!(
+        2,
+        sync_transaction_lines(&client, &mut cursor)
+            .await
+            .unwrap()
+            .len()
+    );
+    assert!(sync_transaction_lines(&client, &mut cursor)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Nothing new, the cursor stays where it was
+    assert_eq!(Some(3), cursor.timestamp(1, ENDPOINT));
+}
+
+#[tokio::test]
+async fn cursor_kept_when_a_page_fails() {
+    let server = MockServer::start().await;
+    mock_page(&server, 5, 6..=1005).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v1/1/sync/{ENDPOINT}")))
+        .and(query_param("$filter", "Timestamp gt 1005L"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+
+    let file = std::env::temp_dir().join(format!("sync-failure-{}.json", std::process::id()));
+    std::fs::write(
+        &file,
+        format!(r#"[{{"division": 1, "endpoint": "{ENDPOINT}", "timestamp": 5}}]"#),
+    )
+    .unwrap();
+    let mut cursor = SyncCursor::load(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    assert!(sync_transaction_lines(&client(&server), &mut cursor)
+        .await
+        .is_err());
+
+    // The first page is read again on the next sync, so no records are skipped
+    assert_eq!(Some(5), cursor.timestamp(1, ENDPOINT));
+}
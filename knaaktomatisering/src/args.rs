@@ -1,5 +1,9 @@
-use clap::{Args, Parser, Subcommand};
+use crate::config::UnconfiguredEventPolicy;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use pretix_request::data_exporter::DateAxis;
 use std::path::PathBuf;
+use time::macros::format_description;
+use time::{Date, Month};
 
 #[derive(Debug, Parser)]
 pub struct ProgramArgs {
@@ -18,6 +22,11 @@ pub struct ProgramArgs {
     /// but don't actually perform them.
     #[clap(long)]
     pub dry_run: bool,
+    /// Save the configuration with its deprecated settings converted.
+    /// Without this flag, they are only converted in memory.
+    /// The original file is kept next to it, with `.bak` appended.
+    #[clap(long)]
+    pub migrate_config: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -25,33 +34,321 @@ pub enum ExecutionMode {
     /// The weekly fun of Mollie, Koala and Pretix.
     /// Applies Pretix payments to the Koala export.
     ///
-    /// Requires the Koala export to be already imported into Exact. This will then add
-    /// all Pretix lines to the sale transaction
+    /// Adds all Pretix lines to the sale transaction created by importing the Koala export,
+    /// e.g. with the `koala` mode. Without a transaction, a new sales entry is created.
+    ///
+    /// The orders are exported once per organizer and booked per event.
+    /// The PDF report is not split: there is one report per organizer, covering all of its live events.
     WeekelijksePlezier(WeekelijksePlezierArgs),
+    /// Import a Koala export into Exact as a new sales entry.
+    /// Optionally adds the Pretix lines to the created entry,
+    /// like `weekelijkse-plezier` does.
+    Koala(KoalaArgs),
+    /// Reconcile a Mollie settlement with the Pretix payments in it.
+    /// Proposes the general journal entry clearing the unassigned payments.
+    Mollie(MollieArgs),
+    /// Pretix utilities.
+    Pretix(PretixArgs),
+    /// Exact Online utilities.
+    Exact(ExactArgs),
+    /// Suggest the configuration of a new Pretix event from its items and tax rates.
+    /// The configuration is written to the configuration file after confirmation.
+    InitEvent(InitEventArgs),
+    /// List the bookings recorded in the ledger.
+    History(HistoryArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct MollieArgs {
+    /// The ID (`stl_...`) or the bank reference of the settlement.
+    /// The bank reference is in the description of the payout on the bank statement.
+    pub settlement: String,
+}
+
+#[derive(Debug, Args)]
+pub struct InitEventArgs {
+    /// The Pretix event ID. Shown in the Pretix
+    /// application as 'Short form'. E.g. `intro-2024-2025`.
+    pub event: String,
+    /// The organizer of the event.
+    /// If not provided, the event is searched for in all accessible organizers.
+    #[clap(long)]
+    pub organizer: Option<String>,
+    /// The code of the GL account the revenue of the event is booked on.
+    /// If not provided, it is chosen from a list.
+    #[clap(long)]
+    pub gl_account: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+    /// Only list bookings of this Pretix event.
+    #[clap(long, short)]
+    pub event: Option<String>,
 }
 
 #[derive(Debug, Args)]
 pub struct WeekelijksePlezierArgs {
     /// The ref of the sale transaction created by importing
     /// the Koala export.
+    /// If not provided, a new sales entry is created.
     #[clap(long, short)]
-    pub transaction_id: i32,
+    pub transaction_id: Option<i32>,
+    /// The description of the new sales entry.
+    /// Defaults to `Pretix {period}`.
+    #[clap(long, conflicts_with = "transaction_id")]
+    pub description: Option<String>,
+    /// The date of the new sales entry, e.g. `2024-10-21`.
+    /// Defaults to the last day of the period.
+    #[clap(long, value_parser = parse_date, conflicts_with = "transaction_id")]
+    pub entry_date: Option<Date>,
+    #[clap(flatten)]
+    pub booking: PretixBookingArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct KoalaArgs {
+    /// Path to the Koala export (CSV).
+    pub file: PathBuf,
+    /// The description of the sales entry.
+    /// Defaults to `Koala {period}`.
+    #[clap(long)]
+    pub description: Option<String>,
+    /// The date of the sales entry, e.g. `2024-10-21`.
+    /// Defaults to the last day of the period.
+    #[clap(long, value_parser = parse_date)]
+    pub entry_date: Option<Date>,
+    /// After creating the sales entry, add the Pretix lines
+    /// for the same period to it.
+    /// The Pretix events are checked before the Koala entry is created.
+    #[clap(long)]
+    pub with_pretix: bool,
+    #[clap(flatten)]
+    pub booking: PretixBookingArgs,
+}
+
+/// Arguments for booking Pretix revenue of a period.
+#[derive(Debug, Args)]
+#[clap(group(ArgGroup::new("period").args(["periods_ago", "from", "week", "month", "quarter"])))]
+pub struct PretixBookingArgs {
     /// How many periods ago to add to the sale order.
     /// A value of 1 indicates the most recent finished period.
     /// This would mean, from two sundays ago to the most recent monday (Koala is inclusive),
     /// for pretix it is up to the most recent sunday (Pretix is exclusive).
     ///
-    /// A value of `0` is not allowed
-    #[clap(long, short, default_value_t = 1)]
-    pub periods_ago: u32,
-    /// The offset in hours with respect to UTC time.
-    /// For NL this is +1 in the winter, +2 in the summer.
-    ///
-    /// Alternatively, you can use the `date` utility to find this export automatically.
-    /// Substitute the value you'd pass to this argument with
-    /// ```
-    /// $(date +"%z" | cut -c 2-)
-    /// ```
+    /// A value of `0` is not allowed.
+    /// Used if no other period is specified, defaults to 1.
+    #[clap(long, short)]
+    pub periods_ago: Option<u32>,
+    /// The first day of the period, e.g. `2024-10-01`.
+    /// Requires `--until`.
+    #[clap(long, requires = "until", value_parser = parse_date)]
+    pub from: Option<Date>,
+    /// The last day of the period, inclusive, e.g. `2024-10-28`.
+    /// Requires `--from`.
+    #[clap(long, requires = "from", value_parser = parse_date)]
+    pub until: Option<Date>,
+    /// An ISO week, e.g. `2024-W42`.
+    #[clap(long, value_parser = parse_week)]
+    pub week: Option<PeriodArg<u8>>,
+    /// A calendar month, e.g. `2024-10`.
+    #[clap(long, value_parser = parse_month)]
+    pub month: Option<PeriodArg<Month>>,
+    /// A calendar quarter, e.g. `2024-Q4`.
+    #[clap(long, value_parser = parse_quarter)]
+    pub quarter: Option<PeriodArg<u8>>,
+    /// The IANA timezone used to determine the current date, e.g. `Europe/Amsterdam`.
+    /// Overrides the timezone in the configuration file.
+    #[clap(long)]
+    pub timezone: Option<String>,
+    /// The date on which orders are assigned to the period.
+    /// One of `order_date` or `last_payment_date`.
+    /// Overrides the date axis in the configuration file.
+    #[clap(long)]
+    pub date_axis: Option<DateAxis>,
+    /// What to do with events that can not be booked, e.g. because they are not configured.
+    /// Overrides the policy in the configuration file.
+    #[clap(long, value_enum)]
+    pub unconfigured_events: Option<UnconfiguredEventPolicy>,
+    /// Book the period even if it overlaps with a booking in the ledger.
+    #[clap(long)]
+    pub force: bool,
+}
+
+/// A numbered period within a year, e.g. week 42 of 2024.
+#[derive(Debug, Clone)]
+pub struct PeriodArg<T> {
+    pub year: i32,
+    pub number: T,
+}
+
+/// Parse a date of the form `2024-10-01`.
+fn parse_date(s: &str) -> Result<Date, String> {
+    let format = format_description!("[year]-[month]-[day]");
+    Date::parse(s, format).map_err(|e| format!("Invalid date '{s}', expected e.g. 2024-10-01: {e}"))
+}
+
+/// Parse an ISO week of the form `2024-W42`.
+fn parse_week(s: &str) -> Result<PeriodArg<u8>, String> {
+    parse_period(s, "W", "2024-W42")
+}
+
+/// Parse a month of the form `2024-10`.
+fn parse_month(s: &str) -> Result<PeriodArg<Month>, String> {
+    let period = parse_period(s, "", "2024-10")?;
+    Ok(PeriodArg {
+        year: period.year,
+        number: Month::try_from(period.number).map_err(|e| format!("Invalid month '{s}': {e}"))?,
+    })
+}
+
+/// Parse a quarter of the form `2024-Q4`.
+fn parse_quarter(s: &str) -> Result<PeriodArg<u8>, String> {
+    parse_period(s, "Q", "2024-Q4")
+}
+
+/// Parse a period of the form `{year}-{prefix}{number}`.
+fn parse_period(s: &str, prefix: &str, example: &str) -> Result<PeriodArg<u8>, String> {
+    let invalid = || format!("Invalid period '{s}', expected e.g. {example}");
+
+    let (year, number) = s.split_once('-').ok_or_else(invalid)?;
+    let number = number.strip_prefix(prefix).ok_or_else(invalid)?;
+
+    Ok(PeriodArg {
+        year: year.parse().map_err(|_| invalid())?,
+        number: number.parse().map_err(|_| invalid())?,
+    })
+}
+
+#[derive(Debug, Args)]
+pub struct PretixArgs {
+    #[clap(subcommand)]
+    pub command: PretixCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PretixCommand {
+    /// Run a Pretix data exporter on an event and
+    /// write the result to a file.
+    Export(PretixExportArgs),
+    /// List Pretix metadata, e.g. to fill in the configuration.
+    List(PretixListArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct PretixListArgs {
+    #[clap(subcommand)]
+    pub resource: PretixListResource,
+    /// How to print the values.
+    #[clap(long, short, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PretixListResource {
+    /// All accessible organizers.
+    Organizers,
+    /// The events of an organizer.
+    Events {
+        /// The organizer. If not provided, the events of all accessible organizers are listed.
+        #[clap(long)]
+        organizer: Option<String>,
+    },
+    /// The items of an event, with their tax rates.
+    Items {
+        /// The Pretix event ID, e.g. `intro-2024-2025`.
+        event: String,
+        /// The organizer of the event.
+        /// If not provided, the event is searched for in all accessible organizers.
+        #[clap(long)]
+        organizer: Option<String>,
+    },
+    /// The data exporters of an event.
+    Exporters {
+        /// The Pretix event ID, e.g. `intro-2024-2025`.
+        event: String,
+        /// The organizer of the event.
+        /// If not provided, the event is searched for in all accessible organizers.
+        #[clap(long)]
+        organizer: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct PretixExportArgs {
+    /// The Pretix event ID. Shown in the Pretix
+    /// application as 'Short form'. E.g. `intro-2024-2025`.
+    pub event: String,
+    /// The identifier of the exporter, e.g. `orderlist` or `checkinlist`.
+    /// If the exporter does not exist, the available exporters are listed.
+    pub exporter: String,
+    /// Parameter for the exporter, in the form `key=value`.
+    /// May be provided multiple times. Providing the same key multiple
+    /// times results in a list of values.
+    /// The values `true` and `false` are passed as booleans.
+    #[clap(long = "param", short, value_parser = parse_key_value)]
+    pub params: Vec<(String, String)>,
+    /// The file to write the export to.
     #[clap(long, short)]
-    pub utc_offset_hours: i32,
+    pub output: PathBuf,
+    /// The organizer of the event.
+    /// If not provided, the event is searched for in all accessible organizers.
+    #[clap(long)]
+    pub organizer: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExactArgs {
+    #[clap(subcommand)]
+    pub command: ExactCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExactCommand {
+    /// List Exact metadata, e.g. to fill in the configuration.
+    List(ExactListArgs),
+    /// List the divisions accessible with the current login.
+    Divisions(ExactDivisionsArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ExactDivisionsArgs {
+    /// How to print the values.
+    #[clap(long, short, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct ExactListArgs {
+    #[clap(subcommand)]
+    pub resource: ExactListResource,
+    /// How to print the values.
+    #[clap(long, short, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExactListResource {
+    /// GL accounts, also known as 'Grootboekrekeningen'.
+    GlAccounts,
+    /// Cost centers, also known as 'Kostenplaatsen'.
+    CostCenters,
+    Journals,
+    VatCodes,
+}
+
+/// How to print listed values.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// An aligned table
+    Table,
+    /// A JSON array
+    Json,
+}
+
+/// Parse an argument of the form `key=value`.
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or(format!("Invalid parameter '{s}', expected 'key=value'"))
 }
@@ -14,7 +14,8 @@ async fn is_exact_authorized(config: &Config) -> color_eyre::Result<bool> {
         {
             debug!("Checking if Exact credentials still work");
 
-            let client = ExactClient::new(&exact_credentials.access_token);
+            let client =
+                ExactClient::new(&exact_credentials.access_token, config.exact.region.clone());
             match accounting_division(&client).await {
                 Ok(_) => true,
                 Err(e) => match e.status() {
@@ -39,6 +40,7 @@ pub async fn ensure_exact_authentication(config: &mut Config) -> color_eyre::Res
         let login_url = exact_request::api::oauth::login_url(
             &config.exact.oauth.client_id,
             &config.exact.oauth.redirect_uri,
+            &config.exact.region,
         );
 
         info!("Please open the following URL and log in: {login_url}");
@@ -54,6 +56,7 @@ pub async fn ensure_exact_authentication(config: &mut Config) -> color_eyre::Res
             &config.exact.oauth.client_id,
             &config.exact.oauth.client_secret,
             &config.exact.oauth.redirect_uri,
+            &config.exact.region,
         )
         .await?;
 
@@ -1,4 +1,7 @@
+use clap::ValueEnum;
+use exact_request::ExactRegion;
 use knaaktomatisering_proc::StringLike;
+use pretix_request::data_exporter::DateAxis;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -13,15 +16,20 @@ pub struct RegexPattern(pub String);
 /// Code for an Exact GL Account,
 /// also known as 'Grootboekrekening'.
 /// E.g. `1302` for unassigned payments.
-#[derive(Debug, Hash, PartialEq, Eq, Deserialize, Serialize, StringLike)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize, StringLike)]
 pub struct ExactGLAccountCode(pub String);
 
 /// Code for an Exact cost center,
 /// also known as `Kostenplaats`.
 /// E.g. `TRX` for transaction fees.
-#[derive(Debug, Hash, PartialEq, Eq, Deserialize, Serialize, StringLike)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize, StringLike)]
 pub struct ExactCostCenterCode(pub String);
 
+/// Code for an Exact cost unit,
+/// also known as `Kostendrager`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize, StringLike)]
+pub struct ExactCostUnitCode(pub String);
+
 /// The Pretix event ID. Shown in the Pretix
 /// application as 'Short form'. E.g. for
 /// the introduction 2024-2025 this is
@@ -33,24 +41,41 @@ pub struct PretixEventId(pub String);
 pub struct Config {
     /// Logging directive as specified by [tracing_subscriber::EnvFilter::from_str].
     pub log: String,
+    /// The IANA timezone used to determine the current date, e.g. to find the last finished week.
+    /// Orders are assigned to days in the timezone of their event, as Pretix does for its reports.
+    /// Defaults to `Europe/Amsterdam`.
+    pub timezone: Option<String>,
+    /// Path to the ledger of booked periods.
+    /// Defaults to `ledger.jsonl` next to the configuration file.
+    pub ledger: Option<PathBuf>,
     /// Built-in OAuth2 web server configuration
     pub web_server: WebServer,
     /// Pretix configuration
     pub pretix: Pretix,
     /// Exact Online configuration
     pub exact: Exact,
+    /// Koala export import configuration.
+    /// Only required for the Koala mode.
+    pub koala: Option<Koala>,
+    /// Mollie configuration.
+    /// Only required for the Mollie mode.
+    pub mollie: Option<Mollie>,
     /// Authorized credentials.
     /// Should not be edited manually
     pub credentials: Option<Credentials>,
+    /// Descriptions of the deprecated settings converted while reading,
+    /// see [Config::migrations]
+    #[serde(skip)]
+    migrations: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Credentials {
     pub pretix: Option<OAuthTokenPair>,
     pub exact: Option<OAuthTokenPair>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct OAuthTokenPair {
     pub access_token: String,
     pub refresh_token: String,
@@ -84,14 +109,59 @@ pub struct Pretix {
     pub url: String,
     /// Event specific configuration
     pub event_specific: HashMap<PretixEventId, PretixEventConfig>,
+    /// How long to wait for exports to complete.
+    /// If not provided, an export is given 10 minutes,
+    /// polling between every 1 and 30 seconds.
+    pub export_polling: Option<ExportPolling>,
+    /// The date on which orders are assigned to a period.
+    /// Used for both the totals and the attached PDF report.
+    /// One of `order_date` or `last_payment_date`.
+    /// Defaults to `last_payment_date`.
+    pub date_axis: Option<DateAxis>,
+    /// The code of the Exact customer account on which new sales entries
+    /// for Pretix revenue are created.
+    /// Required when no existing sales entry is given, and for the Mollie mode,
+    /// which clears the receivable of this customer.
+    pub customer: Option<String>,
+    /// What to do with events that can not be booked,
+    /// e.g. because they are not configured in `event_specific`.
+    /// Defaults to `fail`.
+    pub unconfigured_events: Option<UnconfiguredEventPolicy>,
+}
+
+/// What to do with an event that can not be booked,
+/// because it is not configured or its configuration is incomplete.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnconfiguredEventPolicy {
+    /// Don't book anything
+    #[default]
+    Fail,
+    /// Book the other events
+    SkipWithWarning,
+    /// Book the revenue of the event on the suspense account,
+    /// to be moved to the right account by hand
+    BookToSuspenseAccount,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExportPolling {
+    /// The maximum time in seconds to wait for a single export.
+    /// A stuck Pretix worker will fail the run after this time.
+    pub deadline_secs: u64,
+    /// The time in seconds between the first polls.
+    /// Doubled after every poll, up to `max_interval_secs`.
+    pub initial_interval_secs: u64,
+    /// The maximum time in seconds between polls.
+    pub max_interval_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PretixEventConfig {
     pub gl_account: ExactGLAccountCode,
-    /// The VAT on the event. May be `None`
-    /// if `split_per_product` is true, in which case the information is pulled
-    /// directly from Pretix.
+    /// No longer used: the VAT code of every line is the code for its tax rate,
+    /// see `exact.vat_codes`. Dropped when the configuration is saved with `--migrate-config`.
+    #[serde(default, skip_serializing)]
     pub vat_code: Option<String>,
     /// Whether items in the event should be imported to Exact
     /// as seperate order lines, rather than be combined into one.
@@ -99,26 +169,179 @@ pub struct PretixEventConfig {
     /// For most events, this should be `false`. Notably for
     /// the introduction this should be `true`.
     pub split_per_product: bool,
-    /// The cost centers, also known as 'Kostenplaats' per product in the Pretix event.
-    /// The key of this map may be a Regex pattern. The value should be an Exact
-    /// cost center code. E.g. `TRX` for transaction costs.
-    ///
-    /// If the value for `split_per_product` is set to false, an empty map should be provided.
+    /// How the products are booked if `split_per_product` is true.
+    /// For every setting, the first rule that matches the product and has
+    /// the setting is used. Settings no rule provides fall back to `gl_account`,
+    /// and the VAT code for the tax rate of the product.
+    /// A product that matches no rule and is not ignored can not be booked.
+    /// A rule without conditions matches every product.
+    #[serde(default)]
+    pub rules: Vec<ProductRule>,
+    /// Replaced by `rules`. Entries are converted into rules when the configuration is read,
+    /// the rules are saved with `--migrate-config`.
+    #[serde(default, skip_serializing)]
     pub cost_centers_per_product: HashMap<RegexPattern, ExactCostCenterCode>,
 
     pub ignore_products: Vec<RegexPattern>,
+    /// How the transaction fees are booked.
+    /// Defaults to a single line on the bookkeeping GL account.
+    #[serde(default)]
+    pub fees: FeeAllocation,
+}
+
+/// How the transaction fees of an event are booked.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum FeeAllocation {
+    /// All fees in one line.
+    Single {
+        #[serde(flatten)]
+        target: FeeTarget,
+    },
+    /// The fees are divided over the products, in proportion to their revenue.
+    /// Each share is booked on the cost center of the product,
+    /// unless a cost center is configured.
+    ProRata {
+        #[serde(flatten)]
+        target: FeeTarget,
+    },
+    /// One line per payment provider, e.g. `mollie` or `banktransfer`.
+    PerProvider {
+        #[serde(flatten)]
+        target: FeeTarget,
+        /// Overrides per payment provider.
+        #[serde(default)]
+        providers: HashMap<String, FeeTarget>,
+    },
+}
+
+impl Default for FeeAllocation {
+    fn default() -> Self {
+        Self::Single {
+            target: FeeTarget::default(),
+        }
+    }
+}
+
+/// Where fees are booked.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct FeeTarget {
+    /// Defaults to the bookkeeping GL account.
+    pub gl_account: Option<ExactGLAccountCode>,
+    pub cost_center: Option<ExactCostCenterCode>,
+}
+
+impl PretixEventConfig {
+    /// Convert the `cost_centers_per_product` into rules, sorted by pattern.
+    /// The map has no order, while the first matching rule wins,
+    /// so the order is fixed here and the result should be checked.
+    /// Returns whether any entries were converted.
+    fn migrate_cost_centers(&mut self) -> bool {
+        let mut entries = self.cost_centers_per_product.drain().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+
+        let migrated = !entries.is_empty();
+        for (pattern, cost_center) in entries {
+            self.rules.push(ProductRule {
+                matches: ProductMatch {
+                    item: Some(pattern),
+                    ..Default::default()
+                },
+                cost_center: Some(cost_center),
+                ..Default::default()
+            });
+        }
+
+        migrated
+    }
+
+    /// All GL accounts the event may be booked on.
+    pub fn gl_account_codes(&self) -> Vec<&ExactGLAccountCode> {
+        let fee_targets = match &self.fees {
+            FeeAllocation::Single { target } | FeeAllocation::ProRata { target } => vec![target],
+            FeeAllocation::PerProvider { target, providers } => {
+                std::iter::once(target).chain(providers.values()).collect()
+            }
+        };
+
+        std::iter::once(&self.gl_account)
+            .chain(
+                self.rules
+                    .iter()
+                    .filter_map(|rule| rule.gl_account.as_ref()),
+            )
+            .chain(
+                fee_targets
+                    .into_iter()
+                    .filter_map(|t| t.gl_account.as_ref()),
+            )
+            .collect()
+    }
+}
+
+/// A rule for booking the products of an event.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ProductRule {
+    /// The products the rule applies to.
+    #[serde(rename = "match")]
+    pub matches: ProductMatch,
+    pub gl_account: Option<ExactGLAccountCode>,
+    pub cost_center: Option<ExactCostCenterCode>,
+    pub cost_unit: Option<ExactCostUnitCode>,
+    pub vat_code: Option<String>,
+    /// The description of the line in Exact.
+    /// May contain `{event}`, `{period}`, `{item}`, `{variation}` and `{category}`.
+    /// Defaults to `Pretix {event} | {period} | {item}`.
+    pub description: Option<String>,
+}
+
+/// Which products a rule applies to.
+/// All provided conditions must match, a rule without conditions matches all products.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ProductMatch {
+    /// Regex pattern matched against the item name.
+    pub item: Option<RegexPattern>,
+    /// Regex pattern matched against the category name.
+    pub category: Option<RegexPattern>,
+    /// Regex pattern matched against the variation name.
+    pub variation: Option<RegexPattern>,
+    /// The VAT percentage of the item, e.g. `21.0`.
+    pub tax_rate: Option<f32>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Exact {
     /// OAuth configuration
     pub oauth: OAuth2Config,
+    /// The Exact Online environment, e.g. `netherlands` or `belgium`.
+    /// Any other URL can be used with `{"custom": "https://..."}`.
+    /// Defaults to `netherlands`.
+    #[serde(default)]
+    pub region: ExactRegion,
+    /// The code of the division to book into, also known as 'Administratie'.
+    /// Required for all modes except `exact divisions`, which lists the accessible divisions.
+    pub division: Option<i32>,
     /// Exact GL accounts.
     /// Also known as 'Grootboekrekeningen'
     pub gl_accounts: ExactGlAccounts,
     /// Exact journals
     pub journals: ExactJournals,
+    /// VAT codes to use for a tax rate.
+    /// Only needed where the sales VAT code in Exact can not be determined automatically.
+    #[serde(default)]
     pub vat_codes: Vec<VATCode>,
+    /// Keep the IDs of GL accounts, accounts and cost centers between runs.
+    /// If not set, codes are resolved once per run.
+    pub code_cache: Option<CodeCacheConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CodeCacheConfig {
+    /// Path to the cache file.
+    /// Defaults to `exact_codes.json` next to the configuration file.
+    pub file: Option<PathBuf>,
+    /// The time in hours a cached ID is used before it is resolved again.
+    pub ttl_hours: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -131,6 +354,9 @@ pub struct VATCode {
 pub struct ExactJournals {
     /// The sales journal. Last I checked this is `0302`
     pub sales: String,
+    /// The general journal, also known as 'Memoriaal'.
+    /// Only required for the Mollie mode.
+    pub general: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -141,6 +367,60 @@ pub struct ExactGlAccounts {
     /// The code for bookkeeping.
     /// Last I checked this is `5007`.
     pub bookkeeping: ExactGLAccountCode,
+    /// The suspense account, also known as 'Tussenrekening'.
+    /// Only required for the `book-to-suspense-account` policy.
+    pub suspense: Option<ExactGLAccountCode>,
+    /// The receivables account, also known as 'Debiteuren'.
+    /// Last I checked this is `1300`.
+    /// Only required for the Mollie mode.
+    pub receivables: Option<ExactGLAccountCode>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Mollie {
+    /// An organization access token with the
+    /// `settlements.read` and `payments.read` permissions.
+    pub access_token: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Koala {
+    /// The code of the Exact customer account the Koala sales are booked on.
+    pub customer: String,
+    /// The columns in the Koala export.
+    #[serde(default)]
+    pub columns: KoalaColumns,
+    /// How each product in the Koala export is booked.
+    /// The first entry of which the pattern matches the product name is used.
+    pub products: Vec<KoalaProduct>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KoalaColumns {
+    /// The column containing the product name.
+    /// Defaults to `product`.
+    pub product: String,
+    /// The column containing the amount, excluding VAT.
+    /// Defaults to `amount`.
+    pub amount: String,
+}
+
+impl Default for KoalaColumns {
+    fn default() -> Self {
+        Self {
+            product: "product".to_string(),
+            amount: "amount".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KoalaProduct {
+    /// Regex pattern matched against the product name.
+    pub pattern: RegexPattern,
+    pub gl_account: ExactGLAccountCode,
+    pub cost_center: Option<ExactCostCenterCode>,
+    pub vat_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -157,6 +437,8 @@ pub enum ConfigError {
     Serde(#[from] serde_json::Error),
     #[error("{0}")]
     Io(#[from] std::io::Error),
+    #[error("Invalid configuration file: {0}")]
+    Invalid(String),
 }
 
 impl Config {
@@ -171,19 +453,152 @@ impl Config {
         let mut buf = Vec::new();
         f.read_to_end(&mut buf).await?;
 
-        Ok(serde_json::from_slice(&buf)?)
+        let mut config: Self = serde_json::from_slice(&buf)?;
+        for (event_id, event) in &mut config.pretix.event_specific {
+            if let Some(vat_code) = event.vat_code.take() {
+                config.migrations.push(format!(
+                    "Ignoring VAT code {vat_code} of event {event_id}, the VAT code for the tax rate is used. \
+                    Configure it in 'exact.vat_codes' if needed"
+                ));
+            }
+            if event.migrate_cost_centers() {
+                config.migrations.push(format!(
+                    "Converted 'cost_centers_per_product' of event {event_id} into rules, sorted by pattern. \
+                    The first matching rule is used, check that the order is right"
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The deprecated settings that were converted while reading.
+    /// The conversion is only saved by [Config::migrate].
+    pub fn migrations(&self) -> &[String] {
+        &self.migrations
     }
 
-    /// Write the current configuration to disk.
+    /// Save the converted configuration.
+    /// The original file is kept next to it, with `.bak` appended to its name.
+    /// Returns the path of the original.
     ///
     /// # Errors
     ///
     /// - IO error
     /// - Serialization error
-    pub async fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+    pub async fn migrate<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, ConfigError> {
+        let mut backup = path.as_ref().as_os_str().to_owned();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+
+        fs::copy(path.as_ref(), &backup).await?;
         let buf = serde_json::to_vec_pretty(self)?;
-        let mut f = fs::File::create(path.as_ref()).await?;
-        f.write_all(&buf).await?;
-        Ok(())
+        fs::write(path.as_ref(), buf).await?;
+        Ok(backup)
+    }
+
+    /// Save the credentials in the configuration file.
+    /// The rest of the file is kept as it is, so converted settings are not saved.
+    ///
+    /// # Errors
+    ///
+    /// - IO error
+    /// - (De)serialization error
+    pub async fn write_credentials<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let credentials = serde_json::to_value(&self.credentials)?;
+        update_file(path, |config| {
+            config.insert("credentials".to_string(), credentials);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Add the configuration of an event to the configuration file.
+    /// The rest of the file is kept as it is, so converted settings are not saved.
+    ///
+    /// # Errors
+    ///
+    /// - IO error
+    /// - (De)serialization error
+    /// - If the file has no `pretix.event_specific`
+    pub async fn add_event<P: AsRef<Path>>(
+        path: P,
+        event_id: &PretixEventId,
+        event: &PretixEventConfig,
+    ) -> Result<(), ConfigError> {
+        let event = serde_json::to_value(event)?;
+        update_file(path, |config| {
+            let events = config
+                .get_mut("pretix")
+                .and_then(|pretix| pretix.get_mut("event_specific"))
+                .and_then(|events| events.as_object_mut())
+                .ok_or(ConfigError::Invalid(
+                    "'pretix.event_specific' is missing".to_string(),
+                ))?;
+            events.insert(event_id.0.clone(), event);
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Update the JSON object in the configuration file, leaving the other fields as they are.
+async fn update_file<P, F>(path: P, update: F) -> Result<(), ConfigError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut serde_json::Map<String, serde_json::Value>) -> Result<(), ConfigError>,
+{
+    let mut config: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&fs::read(path.as_ref()).await?)?;
+    update(&mut config)?;
+
+    let mut f = fs::File::create(path.as_ref()).await?;
+    f.write_all(&serde_json::to_vec_pretty(&config)?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn add_event_keeps_the_file() {
+        let path = std::env::temp_dir().join(format!("config-{}.json", std::process::id()));
+        let original = serde_json::json!({
+            "log": "info",
+            "pretix": {
+                "event_specific": {
+                    "intro": {
+                        "gl_account": "8000",
+                        "split_per_product": true,
+                        "cost_centers_per_product": { "^Bier$": "BAR" },
+                        "ignore_products": []
+                    }
+                }
+            }
+        });
+        fs::write(&path, original.to_string()).await.unwrap();
+
+        let event: PretixEventConfig = serde_json::from_value(serde_json::json!({
+            "gl_account": "8100",
+            "split_per_product": false,
+            "ignore_products": []
+        }))
+        .unwrap();
+        Config::add_event(&path, &PretixEventId("gala".to_string()), &event)
+            .await
+            .unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).await.unwrap()).unwrap();
+        fs::remove_file(&path).await.unwrap();
+
+        let events = &written["pretix"]["event_specific"];
+        assert_eq!(
+            events["intro"],
+            original["pretix"]["event_specific"]["intro"]
+        );
+        assert_eq!(events["gala"]["gl_account"], "8100");
+        assert_eq!(written["log"], "info");
     }
 }
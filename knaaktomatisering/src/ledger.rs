@@ -0,0 +1,286 @@
+use crate::args::ProgramArgs;
+use crate::config::Config;
+use exact_filter::Guid;
+use pretix_request::data_exporter::DateAxis;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use time::{Date, OffsetDateTime};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// The ledger file used if none is configured.
+/// Relative to the directory of the configuration file.
+const DEFAULT_LEDGER_FILE: &str = "ledger.jsonl";
+
+/// Local record of all Pretix revenue booked into Exact.
+/// Used to prevent booking the same revenue twice.
+///
+/// Stored as a JSON Lines file, one [LedgerRecord] per line.
+pub struct Ledger {
+    path: PathBuf,
+    records: Vec<LedgerRecord>,
+}
+
+/// A booking of one event for one period.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerRecord {
+    /// When the booking was made
+    #[serde(with = "time::serde::rfc3339")]
+    pub booked_at: OffsetDateTime,
+    /// The Pretix event ID
+    pub event: String,
+    /// The first day of the booked period
+    pub period_start: Date,
+    /// The last day of the booked period, inclusive
+    pub period_end: Date,
+    /// The date on which orders were assigned to the period
+    pub date_axis: DateAxis,
+    /// The entry number of the Exact sales entry
+    pub entry_number: i32,
+    /// The ID of the Exact sales entry
+    pub entry_id: Guid,
+    /// The total value booked, including VAT and excluding fees
+    pub value: f32,
+    /// The VAT included in `value`
+    #[serde(default)]
+    pub vat: f32,
+    /// The total fees booked
+    pub fees: f32,
+    /// The booked lines
+    pub lines: Vec<LedgerLine>,
+}
+
+/// A line booked into Exact.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerLine {
+    pub description: String,
+    pub gl_account: String,
+    /// The value excluding VAT
+    pub amount: f32,
+    #[serde(default)]
+    pub vat: f32,
+}
+
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid ledger record on line {line}: {source}")]
+    Serde {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("{0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+impl Ledger {
+    /// The path of the ledger.
+    /// Either as configured, or [DEFAULT_LEDGER_FILE] next to the configuration file.
+    pub fn path(program_args: &ProgramArgs, config: &Config) -> PathBuf {
+        config.ledger.clone().unwrap_or_else(|| {
+            program_args
+                .config
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join(DEFAULT_LEDGER_FILE)
+        })
+    }
+
+    /// Read the ledger from disk.
+    /// If the file does not exist, the ledger is empty.
+    ///
+    /// # Errors
+    ///
+    /// - IO error
+    /// - If a record could not be deserialized
+    pub async fn read<P: AsRef<Path>>(path: P) -> Result<Self, LedgerError> {
+        let contents = match fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let records = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line).map_err(|source| LedgerError::Serde {
+                    line: idx + 1,
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            records,
+        })
+    }
+
+    /// All records in the ledger, oldest first.
+    pub fn records(&self) -> &[LedgerRecord] {
+        &self.records
+    }
+
+    /// All records of `event` on `date_axis` whose period overlaps with `start` through `end`, inclusive.
+    pub fn overlapping(
+        &self,
+        event: &str,
+        start: Date,
+        end: Date,
+        date_axis: DateAxis,
+    ) -> Vec<&LedgerRecord> {
+        self.records
+            .iter()
+            .filter(|record| {
+                record.event.eq(event)
+                    && record.date_axis == date_axis
+                    && record.period_start <= end
+                    && record.period_end >= start
+            })
+            .collect()
+    }
+
+    /// All records of `event` booked on another date axis than `date_axis`.
+    ///
+    /// The periods of different axes can not be compared: an order placed in one period
+    /// may be paid in the next. Mixing axes for one event can therefore book an order twice, or not at all.
+    pub fn other_axis(&self, event: &str, date_axis: DateAxis) -> Vec<&LedgerRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.event.eq(event) && record.date_axis != date_axis)
+            .collect()
+    }
+
+    /// Append a record to the ledger and write it to disk.
+    ///
+    /// # Errors
+    ///
+    /// - IO error
+    /// - Serialization error
+    pub async fn append(&mut self, record: LedgerRecord) -> Result<(), LedgerError> {
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        f.write_all(&line).await?;
+        f.flush().await?;
+
+        self.records.push(record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ledger, LedgerRecord};
+    use exact_filter::Guid;
+    use pretix_request::data_exporter::DateAxis;
+    use std::path::PathBuf;
+    use time::macros::{date, datetime};
+    use time::Date;
+
+    const LAST_PAYMENT: DateAxis = DateAxis::LastPaymentDate;
+
+    fn record(event: &str, start: Date, end: Date) -> LedgerRecord {
+        LedgerRecord {
+            booked_at: datetime!(2024-10-21 12:00 UTC),
+            event: event.to_string(),
+            period_start: start,
+            period_end: end,
+            date_axis: LAST_PAYMENT,
+            entry_number: 1,
+            entry_id: Guid::new("00000000-0000-0000-0000-000000000000"),
+            value: 10.0,
+            vat: 0.0,
+            fees: 0.5,
+            lines: vec![],
+        }
+    }
+
+    #[test]
+    fn overlapping() {
+        let ledger = Ledger {
+            path: PathBuf::new(),
+            records: vec![record(
+                "intro",
+                date!(2024 - 10 - 14),
+                date!(2024 - 10 - 20),
+            )],
+        };
+
+        assert_eq!(
+            ledger
+                .overlapping(
+                    "intro",
+                    date!(2024 - 10 - 20),
+                    date!(2024 - 10 - 26),
+                    LAST_PAYMENT
+                )
+                .len(),
+            1
+        );
+        assert!(ledger
+            .overlapping(
+                "intro",
+                date!(2024 - 10 - 21),
+                date!(2024 - 10 - 27),
+                LAST_PAYMENT
+            )
+            .is_empty());
+        assert!(ledger
+            .overlapping(
+                "gala",
+                date!(2024 - 10 - 14),
+                date!(2024 - 10 - 20),
+                LAST_PAYMENT
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn other_date_axis() {
+        let ledger = Ledger {
+            path: PathBuf::new(),
+            records: vec![record(
+                "intro",
+                date!(2024 - 10 - 14),
+                date!(2024 - 10 - 20),
+            )],
+        };
+
+        // A booking on another axis is not an overlap of the same orders, but it is reported
+        assert!(ledger
+            .overlapping(
+                "intro",
+                date!(2024 - 10 - 14),
+                date!(2024 - 10 - 20),
+                DateAxis::OrderDate
+            )
+            .is_empty());
+        assert_eq!(1, ledger.other_axis("intro", DateAxis::OrderDate).len());
+        assert!(ledger.other_axis("intro", LAST_PAYMENT).is_empty());
+        assert!(ledger.other_axis("gala", DateAxis::OrderDate).is_empty());
+    }
+
+    #[test]
+    fn record_roundtrip() {
+        let line = serde_json::to_string(&record(
+            "intro",
+            date!(2024 - 10 - 14),
+            date!(2024 - 10 - 20),
+        ))
+        .unwrap();
+        assert!(line.contains("\"period_start\":\"2024-10-14\""));
+
+        let parsed: LedgerRecord = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed.period_end, date!(2024 - 10 - 20));
+    }
+}
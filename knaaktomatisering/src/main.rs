@@ -1,15 +1,28 @@
-use crate::args::{ExecutionMode, ProgramArgs};
+use crate::args::{ExactArgs, ExactCommand, ExecutionMode, ProgramArgs};
 use crate::auth::ensure_authentication;
 use crate::config::{Config, Credentials, OAuthTokenPair};
+use crate::modes::exact::divisions::print_divisions;
+use crate::modes::exact::Exact;
+use crate::modes::history::print_history;
+use crate::modes::init_event::InitEvent;
+use crate::modes::koala::Koala;
+use crate::modes::mollie::Mollie;
+use crate::modes::pretix::Pretix;
 use crate::modes::weekelijkse_plezier::WeekelijksePlezier;
 use crate::modes::{ExternalClients, Mode};
 use clap::Parser;
 use color_eyre::eyre::Error;
+use exact_request::api::division::list_divisions;
 use exact_request::api::me::accounting_division;
+use exact_request::cache::CodeCache;
 use exact_request::ExactClient;
+use mollie_request::MollieClient;
+use pretix_request::data_exporter::ExportPolling;
 use pretix_request::PretixClient;
+use std::path::Path;
 use std::str::FromStr;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::fmt::layer;
 use tracing_subscriber::layer::SubscriberExt;
@@ -19,8 +32,13 @@ use tracing_subscriber::{registry, EnvFilter};
 mod args;
 mod auth;
 mod config;
+mod ledger;
 mod modes;
 
+/// The Exact code cache file used if none is configured.
+/// Relative to the directory of the configuration file.
+const DEFAULT_CODE_CACHE_FILE: &str = "exact_codes.json";
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
@@ -39,13 +57,41 @@ async fn main() -> color_eyre::Result<()> {
     );
     info!("De centjesautomaat van Sticky");
 
+    // Deprecated settings are converted when reading,
+    // they are only saved if the user asks for it
+    for migration in config.migrations() {
+        warn!("{migration}");
+    }
+    if !config.migrations().is_empty() {
+        if prog_args.migrate_config {
+            let backup = config.migrate(&prog_args.config).await?;
+            info!(
+                "Saved the converted configuration, the original is kept in {}",
+                backup.display()
+            );
+        } else {
+            warn!(
+                "The configuration was only converted in memory. Use '--migrate-config' to save it"
+            );
+        }
+    }
+
+    // The history only reads the local ledger,
+    // there's no need to authorize for it.
+    if let Some(ExecutionMode::History(args)) = &prog_args.mode {
+        return print_history(args, &prog_args, &config).await;
+    }
+
     // Required for some SSL stuff. Must be done at most once
     // per program, so why not do it right at the start.
     init_rustls()?;
 
     // Check authentication & update config with new tokens
+    let credentials = config.credentials.clone();
     ensure_authentication(&mut config).await?;
-    config.write(&prog_args.config).await?;
+    if config.credentials != credentials {
+        config.write_credentials(&prog_args.config).await?;
+    }
 
     // We have this flag because you often
     // bind to port 443 (and that's the default behaviour),
@@ -57,52 +103,165 @@ async fn main() -> color_eyre::Result<()> {
         return Ok(());
     }
 
+    // The divisions are listed to choose one for the configuration,
+    // so this must work before a division is configured.
+    if let Some(ExecutionMode::Exact(ExactArgs {
+        command: ExactCommand::Divisions(args),
+    })) = &prog_args.mode
+    {
+        let mut exact_client = exact_client(&config);
+        exact_client.set_division(accounting_division(&exact_client).await?);
+        return print_divisions(args, &config, &exact_client).await;
+    }
+
+    let mode = prog_args.mode.as_ref().expect("No execution mode provided");
+
     // Initialize all required external clients like
     // Exact Online and Pretix.
-    let clients = init_external_clients(&config).await?;
+    // The Pretix utilities don't use Exact, so they work without a valid division.
+    let uses_exact = !matches!(mode, ExecutionMode::Pretix(_));
+    let clients = init_external_clients(&prog_args, &config, uses_exact).await?;
 
     // Run the program in the desired mode.
-    match &prog_args.mode.as_ref().expect("No execution mode provided") {
+    let result = match mode {
         ExecutionMode::WeekelijksePlezier(args) => {
             WeekelijksePlezier::execute_mode(args, &prog_args, &config, &clients).await
         }
-    }?;
+        ExecutionMode::Koala(args) => {
+            Koala::execute_mode(args, &prog_args, &config, &clients).await
+        }
+        ExecutionMode::Mollie(args) => {
+            Mollie::execute_mode(args, &prog_args, &config, &clients).await
+        }
+        ExecutionMode::Pretix(args) => {
+            Pretix::execute_mode(args, &prog_args, &config, &clients).await
+        }
+        ExecutionMode::Exact(args) => {
+            Exact::execute_mode(args, &prog_args, &config, &clients).await
+        }
+        ExecutionMode::InitEvent(args) => {
+            InitEvent::execute_mode(args, &prog_args, &config, &clients).await
+        }
+        ExecutionMode::History(_) => unreachable!("History is handled before authorization"),
+    };
 
-    Ok(())
+    // Codes resolved before a failure are still valid.
+    // Failing to save them must not hide the outcome of the mode
+    if let Err(e) = clients.exact.code_cache().save() {
+        warn!("Could not save the Exact code cache: {e}");
+    }
+
+    result
 }
 
 /// Initialize all external clients.
 /// Requires all clients have a valid access token configured.
+/// The Exact division is only selected if `uses_exact` is set,
+/// otherwise requests to Exact fail.
 ///
 /// # Errors
 ///
 /// If a client could not be initialized
-async fn init_external_clients(config: &Config) -> color_eyre::Result<ExternalClients> {
+async fn init_external_clients(
+    prog_args: &ProgramArgs,
+    config: &Config,
+    uses_exact: bool,
+) -> color_eyre::Result<ExternalClients> {
     let pretix_client = pretix_client(config);
 
     let mut exact_client = exact_client(config);
-    // We need to query the account division, we use this is in all subsequent requests.
-    exact_client.set_division(accounting_division(&exact_client).await?);
+    if uses_exact {
+        select_division(&mut exact_client, config).await?;
+    }
+
+    if let Some(code_cache) = &config.exact.code_cache {
+        let file = code_cache.file.clone().unwrap_or_else(|| {
+            prog_args
+                .config
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join(DEFAULT_CODE_CACHE_FILE)
+        });
+        exact_client.set_code_cache(CodeCache::load(
+            file,
+            Duration::from_secs(code_cache.ttl_hours * 60 * 60),
+        )?);
+    }
 
     Ok(ExternalClients {
         pretix: pretix_client,
         exact: exact_client,
+        mollie: config
+            .mollie
+            .as_ref()
+            .map(|mollie| MollieClient::new(&mollie.access_token)),
     })
 }
 
+/// Set the configured division on the Exact client, it is used in all subsequent requests.
+///
+/// # Errors
+///
+/// - If no division is configured
+/// - If the division is not accessible with the current login
+async fn select_division(
+    exact_client: &mut ExactClient,
+    config: &Config,
+) -> color_eyre::Result<()> {
+    let division = config.exact.division.ok_or(Error::msg(
+        "No Exact division configured. Run 'exact divisions' to list the accessible divisions",
+    ))?;
+
+    // Divisions can only be listed from within a division.
+    // The division last opened by the user in Exact will do.
+    exact_client.set_division(accounting_division(exact_client).await?);
+    let divisions = list_divisions(exact_client).await?;
+    let Some(selected) = divisions.iter().find(|d| d.code == division) else {
+        return Err(Error::msg(format!(
+            "Exact division {division} is not accessible with the current login. Run 'exact divisions' to list the accessible divisions"
+        )));
+    };
+    if selected.is_blocked() {
+        return Err(Error::msg(format!(
+            "Exact division {division} ({}) is blocked (status {})",
+            selected.description, selected.blocking_status
+        )));
+    }
+
+    info!(
+        "Using Exact division {} ({})",
+        selected.code, selected.description
+    );
+    exact_client.set_division(division);
+    Ok(())
+}
+
 /// Create an Exact client.
 /// Requires the access token to be set.
 fn exact_client(config: &Config) -> ExactClient {
-    ExactClient::new(access_token(config, |c| &c.exact))
+    ExactClient::new(
+        access_token(config, |c| &c.exact),
+        config.exact.region.clone(),
+    )
 }
 
 /// Create a pretix client.
 /// Requires the access token to be set.
 fn pretix_client(config: &Config) -> PretixClient {
-    PretixClient::new(
+    let mut client = PretixClient::new(
         access_token(config, |c| &c.pretix),
         config.pretix.url.clone(),
-    )
+    );
+
+    if let Some(polling) = &config.pretix.export_polling {
+        client.set_export_polling(ExportPolling {
+            deadline: Duration::from_secs(polling.deadline_secs),
+            initial_interval: Duration::from_secs(polling.initial_interval_secs),
+            max_interval: Duration::from_secs(polling.max_interval_secs),
+        });
+    }
+
+    client
 }
 
 /// Retrieve an application's access token from the configuration file.
@@ -116,7 +275,7 @@ where
 {
     let credentials = &config.credentials.as_ref().unwrap();
 
-    let app_credentials = f(*credentials);
+    let app_credentials = f(credentials);
 
     app_credentials.as_ref().unwrap().access_token.clone()
 }
@@ -140,7 +299,8 @@ fn init_rustls() -> color_eyre::Result<()> {
 fn install_tracing<S: AsRef<str>>(directive: S) -> color_eyre::Result<()> {
     registry()
         .with(EnvFilter::from_str(directive.as_ref())?)
-        .with(layer())
+        // Logs go to stderr, so listed values can be piped
+        .with(layer().with_writer(std::io::stderr))
         .with(ErrorLayer::default())
         .try_init()?;
     Ok(())
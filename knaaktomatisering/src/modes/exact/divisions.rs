@@ -0,0 +1,35 @@
+use crate::args::ExactDivisionsArgs;
+use crate::config::Config;
+use crate::modes::list::print_list;
+use color_eyre::Result;
+use exact_request::api::division::list_divisions;
+use exact_request::ExactClient;
+
+/// Print the divisions accessible with the current login.
+/// Runs before the configured division is selected, so it can be used to choose one.
+pub async fn print_divisions(
+    args: &ExactDivisionsArgs,
+    config: &Config,
+    exact_client: &ExactClient,
+) -> Result<()> {
+    let divisions = list_divisions(exact_client).await?;
+    print_list(
+        args.format,
+        &["Code", "Description", "Main", "Blocked", "Configured"],
+        &divisions,
+        |d| {
+            vec![
+                d.code.to_string(),
+                d.description.clone(),
+                if d.main { "yes" } else { "no" }.to_string(),
+                if d.is_blocked() { "yes" } else { "no" }.to_string(),
+                if config.exact.division == Some(d.code) {
+                    "yes"
+                } else {
+                    "no"
+                }
+                .to_string(),
+            ]
+        },
+    )
+}
@@ -0,0 +1,92 @@
+use crate::args::{ExactListArgs, ExactListResource, ProgramArgs};
+use crate::config::Config;
+use crate::modes::list::print_list;
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::Result;
+use exact_request::api::cost_center::list_cost_centers;
+use exact_request::api::gl_account::list_gl_accounts;
+use exact_request::api::journal::list_journals;
+use exact_request::api::vat_code::list_vat_codes;
+
+pub struct ExactList;
+
+impl Mode for ExactList {
+    type Args = ExactListArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        _program_args: &ProgramArgs,
+        _config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        let exact_client = &external_clients.exact;
+
+        match args.resource {
+            ExactListResource::GlAccounts => {
+                let gl_accounts = list_gl_accounts(exact_client).await?;
+                print_list(
+                    args.format,
+                    &["Code", "Description", "Type", "Balance"],
+                    &gl_accounts,
+                    |a| {
+                        vec![
+                            a.code.clone(),
+                            a.description.clone(),
+                            a.type_description.clone(),
+                            a.balance_type.clone(),
+                        ]
+                    },
+                )
+            }
+            ExactListResource::CostCenters => {
+                let cost_centers = list_cost_centers(exact_client).await?;
+                print_list(
+                    args.format,
+                    &["Code", "Description", "Active"],
+                    &cost_centers,
+                    |c| vec![c.code.clone(), c.description.clone(), c.active.to_string()],
+                )
+            }
+            ExactListResource::Journals => {
+                let journals = list_journals(exact_client).await?;
+                print_list(
+                    args.format,
+                    &["Code", "Description", "Type"],
+                    &journals,
+                    |j| {
+                        vec![
+                            j.code.clone(),
+                            j.description.clone(),
+                            j.journal_type.to_string(),
+                        ]
+                    },
+                )
+            }
+            ExactListResource::VatCodes => {
+                let vat_codes = list_vat_codes(exact_client).await?;
+                print_list(
+                    args.format,
+                    &[
+                        "Code",
+                        "Description",
+                        "Percentage",
+                        "Type",
+                        "Usage",
+                        "Blocked",
+                    ],
+                    &vat_codes,
+                    |v| {
+                        vec![
+                            v.code.trim().to_string(),
+                            v.description.clone(),
+                            format!("{:.2}%", v.percentage * 100.0),
+                            v.vat_type.clone(),
+                            v.transaction_type.clone(),
+                            if v.is_blocked { "yes" } else { "no" }.to_string(),
+                        ]
+                    },
+                )
+            }
+        }
+    }
+}
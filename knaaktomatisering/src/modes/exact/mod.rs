@@ -0,0 +1,29 @@
+use crate::args::{ExactArgs, ExactCommand, ProgramArgs};
+use crate::config::Config;
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::Result;
+
+pub mod divisions;
+pub mod list;
+
+pub struct Exact;
+
+impl Mode for Exact {
+    type Args = ExactArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        program_args: &ProgramArgs,
+        config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        match &args.command {
+            ExactCommand::List(args) => {
+                list::ExactList::execute_mode(args, program_args, config, external_clients).await
+            }
+            ExactCommand::Divisions(_) => {
+                unreachable!("Divisions are listed before a division is selected")
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::args::{HistoryArgs, ProgramArgs};
+use crate::config::Config;
+use crate::ledger::Ledger;
+use color_eyre::Result;
+use tracing::info;
+
+/// Print the bookings recorded in the ledger.
+/// Only requires the local ledger, so no authorization is needed.
+pub async fn print_history(
+    args: &HistoryArgs,
+    program_args: &ProgramArgs,
+    config: &Config,
+) -> Result<()> {
+    let path = Ledger::path(program_args, config);
+    let ledger = Ledger::read(&path).await?;
+    info!("Reading ledger {}", path.display());
+
+    let records = ledger
+        .records()
+        .iter()
+        .filter(|record| {
+            args.event
+                .as_ref()
+                .is_none_or(|event| record.event.eq(event))
+        })
+        .collect::<Vec<_>>();
+
+    if records.is_empty() {
+        info!("No bookings found");
+        return Ok(());
+    }
+
+    for record in records {
+        println!(
+            "{} {} {} - {} ({}) entry {}: €{:.2} + €{:.2} fees",
+            record.booked_at.date(),
+            record.event,
+            record.period_start,
+            record.period_end,
+            record.date_axis,
+            record.entry_number,
+            record.value,
+            record.fees,
+        );
+
+        for line in &record.lines {
+            println!(
+                "    {} {} €{:.2}",
+                line.gl_account, line.description, line.amount
+            );
+        }
+    }
+
+    Ok(())
+}
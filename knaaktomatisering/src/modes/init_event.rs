@@ -0,0 +1,255 @@
+use crate::args::{InitEventArgs, ProgramArgs};
+use crate::config::{
+    Config, ExactCostCenterCode, ExactGLAccountCode, FeeAllocation, PretixEventConfig,
+    PretixEventId, ProductMatch, ProductRule, RegexPattern,
+};
+use crate::modes::pretix::{localized, resolve_organizer};
+use crate::modes::weekelijkse_plezier::vat::VatCodes;
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use exact_request::api::cost_center::list_cost_centers;
+use exact_request::api::gl_account::list_gl_accounts;
+use pretix_request::events::EventId;
+use pretix_request::items::Item;
+use std::collections::HashMap;
+use std::io::{stdin, stdout, Write};
+use tracing::{info, warn};
+
+pub struct InitEvent;
+
+impl Mode for InitEvent {
+    type Args = InitEventArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        program_args: &ProgramArgs,
+        config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        let pretix_client = &external_clients.pretix;
+        let exact_client = &external_clients.exact;
+
+        let event_id = PretixEventId(args.event.clone());
+        if config.pretix.event_specific.contains_key(&event_id) {
+            return Err(Error::msg(format!(
+                "Event {} is already configured",
+                args.event
+            )));
+        }
+
+        let event = EventId(args.event.clone());
+        let organizer = resolve_organizer(pretix_client, args.organizer.as_deref(), &event).await?;
+        let items = Item::list(pretix_client, &organizer, &event)
+            .await?
+            .into_iter()
+            .filter(|item| item.active)
+            .collect::<Vec<_>>();
+
+        info!("Event {organizer}/{event} has {} active items", items.len());
+        for item in &items {
+            info!("Item: {} at {:.2}%", localized(&item.name), item.tax_rate);
+        }
+
+        // Choose the GL account the revenue is booked on
+        let gl_accounts = list_gl_accounts(exact_client)
+            .await?
+            .into_iter()
+            .filter(|account| account.balance_type.eq("W"))
+            .collect::<Vec<_>>();
+        let gl_account = match &args.gl_account {
+            Some(code) => code.clone(),
+            None => {
+                for (i, account) in gl_accounts.iter().enumerate() {
+                    println!("{:>3}. {} {}", i + 1, account.code, account.description);
+                }
+
+                let answer = prompt("GL account (number or code)")?;
+                match answer.parse::<usize>() {
+                    Ok(n) if (1..=gl_accounts.len()).contains(&n) => {
+                        gl_accounts[n - 1].code.clone()
+                    }
+                    _ => answer,
+                }
+            }
+        };
+
+        if !gl_accounts
+            .iter()
+            .any(|account| account.code.eq(&gl_account))
+        {
+            return Err(Error::msg(format!(
+                "GL account {gl_account} does not exist or is not a profit and loss account"
+            )));
+        }
+
+        // Events with a single item are booked as a whole
+        let split_per_product = items.len() > 1;
+
+        let mut rules = Vec::new();
+        let mut ignore_products = Vec::new();
+        if split_per_product {
+            let cost_centers = list_cost_centers(exact_client)
+                .await?
+                .into_iter()
+                .filter(|cost_center| cost_center.active)
+                .collect::<Vec<_>>();
+            for cost_center in &cost_centers {
+                println!("{} {}", cost_center.code, cost_center.description);
+            }
+
+            for item in &items {
+                let name = localized(&item.name);
+                let code = prompt(&format!("Cost center for '{name}' (empty to ignore)"))?;
+
+                if code.is_empty() {
+                    ignore_products.push(item_pattern(&name));
+                    continue;
+                }
+
+                if !cost_centers.iter().any(|c| c.code.eq(&code)) {
+                    return Err(Error::msg(format!("Cost center {code} does not exist")));
+                }
+
+                rules.push(ProductRule {
+                    matches: ProductMatch {
+                        item: Some(item_pattern(&name)),
+                        ..Default::default()
+                    },
+                    cost_center: Some(ExactCostCenterCode(code)),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // The lines are booked on the VAT code for their tax rate
+        let vat_codes = VatCodes::fetch(exact_client, &config.exact.vat_codes).await?;
+        for (tax_rate, e) in unresolved_tax_rates(&items, &vat_codes) {
+            warn!("No VAT code for the {tax_rate:.2}% items: {e}");
+        }
+
+        let event_config = PretixEventConfig {
+            gl_account: ExactGLAccountCode(gl_account),
+            vat_code: None,
+            split_per_product,
+            rules,
+            cost_centers_per_product: HashMap::new(),
+            fees: FeeAllocation::default(),
+            ignore_products,
+        };
+
+        println!("{}", serde_json::to_string_pretty(&event_config)?);
+
+        if program_args.dry_run {
+            info!("Flag '--dry-run' set. Not writing the configuration");
+            return Ok(());
+        }
+
+        if !prompt("Write this configuration? [y/N]")?.eq_ignore_ascii_case("y") {
+            info!("Not writing the configuration");
+            return Ok(());
+        }
+
+        // Only this event is added to the file, the rest of it is kept as it is
+        Config::add_event(&program_args.config, &event_id, &event_config).await?;
+        info!("Added event {event} to {}", program_args.config.display());
+
+        Ok(())
+    }
+}
+
+/// Ask the user a question on the terminal.
+/// Returns the trimmed answer.
+fn prompt(question: &str) -> Result<String> {
+    print!("{question}: ");
+    stdout().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// A pattern matching exactly the item name.
+fn item_pattern(name: &str) -> RegexPattern {
+    RegexPattern(format!("^{}$", regex::escape(name)))
+}
+
+/// The tax rates of the items for which no VAT code can be resolved, with the reason.
+fn unresolved_tax_rates(items: &[Item], vat_codes: &VatCodes) -> Vec<(f32, String)> {
+    let mut tax_rates: Vec<f32> = Vec::new();
+    for item in items {
+        if !tax_rates.contains(&item.tax_rate) {
+            tax_rates.push(item.tax_rate);
+        }
+    }
+
+    tax_rates
+        .into_iter()
+        .filter_map(|tax_rate| {
+            vat_codes
+                .for_tax_rate(tax_rate)
+                .err()
+                .map(|e| (tax_rate, e.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::VATCode;
+
+    fn item(name: &str, tax_rate: f32) -> Item {
+        Item {
+            id: 1,
+            name: HashMap::from([("en".to_string(), name.to_string())]),
+            active: true,
+            category: None,
+            default_price: 10.0,
+            tax_rate,
+            tax_rule: None,
+        }
+    }
+
+    fn vat_overrides() -> Vec<VATCode> {
+        vec![
+            VATCode {
+                percentage: 21.0,
+                code: "2".to_string(),
+            },
+            VATCode {
+                percentage: 9.0,
+                code: "1".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn pattern_matches_item_exactly() {
+        let pattern = item_pattern("Ticket (member)");
+        let regex = regex::Regex::new(pattern.as_ref()).unwrap();
+
+        assert!(regex.is_match("Ticket (member)"));
+        assert!(!regex.is_match("Ticket (member) + drinks"));
+    }
+
+    #[test]
+    fn tax_rates_without_vat_code() {
+        let overrides = vat_overrides();
+        let vat_codes = VatCodes::new(&overrides, Vec::new());
+
+        let items = [item("Ticket", 9.0), item("Merch", 21.0)];
+        assert!(unresolved_tax_rates(&items, &vat_codes).is_empty());
+
+        let items = [
+            item("Ticket", 9.0),
+            item("Donation", 0.0),
+            item("Gift", 0.0),
+        ];
+        let unresolved = unresolved_tax_rates(&items, &vat_codes)
+            .into_iter()
+            .map(|(tax_rate, _)| tax_rate)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0.0], unresolved);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::config::KoalaColumns;
+use crate::modes::weekelijkse_plezier::rounding::{from_cents, to_cents};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+
+/// The total sales of one product in the Koala export.
+#[derive(Debug, PartialEq)]
+pub struct KoalaSale {
+    pub product: String,
+    pub amount: f32,
+}
+
+/// Parse a Koala export (CSV).
+/// Rows of the same product are summed, the order of first appearance is kept.
+/// Both `,` and `;` are supported as delimiter.
+///
+/// # Errors
+///
+/// - If a configured column is missing
+/// - If an amount could not be parsed
+/// - If the CSV is malformed
+pub fn parse_export(contents: &str, columns: &KoalaColumns) -> Result<Vec<KoalaSale>> {
+    let header = contents.lines().next().unwrap_or_default();
+    let delimiter = if header.matches(';').count() > header.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+            .ok_or(Error::msg(format!(
+                "Column '{name}' does not exist in the Koala export"
+            )))
+    };
+    let product_column = column(&columns.product)?;
+    let amount_column = column(&columns.amount)?;
+
+    // Summed in cents, so many rows don't accumulate float errors
+    let mut sales: Vec<(String, i64)> = Vec::new();
+    for (idx, record) in reader.records().enumerate() {
+        let record = record?;
+        let product = record.get(product_column).unwrap_or_default();
+        let raw_amount = record.get(amount_column).unwrap_or_default();
+        let amount = parse_amount(raw_amount).ok_or(Error::msg(format!(
+            "Invalid or ambiguous amount '{raw_amount}' on row {}",
+            idx + 1
+        )))?;
+
+        let cents = to_cents(amount);
+        match sales.iter_mut().find(|(name, _)| name.eq(product)) {
+            Some((_, total)) => *total += cents,
+            None => sales.push((product.to_string(), cents)),
+        }
+    }
+
+    Ok(sales
+        .into_iter()
+        .map(|(product, cents)| KoalaSale {
+            product,
+            amount: from_cents(cents),
+        })
+        .collect())
+}
+
+/// Parse an amount like `12.50`, `12,50`, `€ 1.234,50`, `1,234.50` or `-3,00`.
+/// The last `.` or `,` is the decimal separator if one or two digits follow it,
+/// the other is the thousands separator.
+/// Amounts that can be read both ways, like `1.234`, are rejected.
+fn parse_amount(s: &str) -> Option<f32> {
+    let s = s.replace(['€', ' '], "");
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.as_str()),
+    };
+
+    let (integer, fraction, thousands) = match s.rfind(['.', ',']) {
+        None => (s, "", None),
+        Some(idx) => {
+            let separator = s[idx..].chars().next()?;
+            let other = if separator == '.' { ',' } else { '.' };
+            match s.len() - idx - 1 {
+                // A decimal separator
+                1 | 2 => (&s[..idx], &s[idx + 1..], Some(other)),
+                // A thousands separator, if it is used more than once
+                3 if s.matches(separator).count() > 1 => (s, "", Some(separator)),
+                _ => return None,
+            }
+        }
+    };
+
+    let integer = match thousands {
+        Some(separator) => parse_thousands(integer, separator)?,
+        None => integer.to_string(),
+    };
+    if integer.is_empty()
+        || !integer.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let amount: f32 = format!("{integer}.{fraction}")
+        .trim_end_matches('.')
+        .parse()
+        .ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// Remove the thousands separators from `s`, checking that they separate groups of three digits.
+fn parse_thousands(s: &str, separator: char) -> Option<String> {
+    if !s.contains(separator) {
+        return Some(s.to_string());
+    }
+
+    let mut groups = s.split(separator);
+    let first = groups.next()?;
+    if first.is_empty() || first.len() > 3 {
+        return None;
+    }
+
+    groups.try_fold(first.to_string(), |mut integer, group| {
+        (group.len() == 3).then(|| {
+            integer.push_str(group);
+            integer
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_amount, parse_export, KoalaSale};
+    use crate::config::KoalaColumns;
+
+    #[test]
+    fn amounts() {
+        assert_eq!(parse_amount("12.50"), Some(12.5));
+        assert_eq!(parse_amount("12,50"), Some(12.5));
+        assert_eq!(parse_amount("€ 1.234,50"), Some(1234.5));
+        assert_eq!(parse_amount("-3,00"), Some(-3.0));
+        assert_eq!(parse_amount("twelve"), None);
+    }
+
+    #[test]
+    fn separators() {
+        assert_eq!(parse_amount("1,234.50"), Some(1234.5));
+        assert_eq!(parse_amount("1.234.567"), Some(1234567.0));
+        assert_eq!(parse_amount("1234"), Some(1234.0));
+        assert_eq!(parse_amount("1234,50"), Some(1234.5));
+        assert_eq!(parse_amount("12.5"), Some(12.5));
+    }
+
+    #[test]
+    fn ambiguous_amounts() {
+        assert_eq!(parse_amount("1.234"), None);
+        assert_eq!(parse_amount("1,234"), None);
+        assert_eq!(parse_amount("1,234,50"), None);
+        assert_eq!(parse_amount("1.23.450"), None);
+        assert_eq!(parse_amount("12.3456"), None);
+    }
+
+    #[test]
+    fn semicolon_export() {
+        let contents = "Product;Amount\nBier;10,00\nCola;2,50\nBier;5,00\n";
+        let sales = parse_export(contents, &KoalaColumns::default()).unwrap();
+        assert_eq!(
+            sales,
+            vec![
+                KoalaSale {
+                    product: "Bier".to_string(),
+                    amount: 15.0
+                },
+                KoalaSale {
+                    product: "Cola".to_string(),
+                    amount: 2.5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn summed_in_cents() {
+        let contents = format!("Product,Amount\n{}", "Bier,0.10\n".repeat(1000));
+        let sales = parse_export(&contents, &KoalaColumns::default()).unwrap();
+        assert_eq!(
+            sales,
+            vec![KoalaSale {
+                product: "Bier".to_string(),
+                amount: 100.0
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_column() {
+        let contents = "name,price\nBier,10.00\n";
+        assert!(parse_export(contents, &KoalaColumns::default()).is_err());
+    }
+}
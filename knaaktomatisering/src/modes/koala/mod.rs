@@ -0,0 +1,149 @@
+use crate::args::{KoalaArgs, ProgramArgs};
+use crate::config::Config;
+use crate::modes::weekelijkse_plezier::{
+    booking_period, prepare_pretix, SalesEntryRef, SalesEntryTarget,
+};
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use exact_request::api::account::get_account_by_code;
+use exact_request::api::gl_account::get_gl_account_by_code;
+use exact_request::api::sales_entry::{create_sales_entry, NewSalesEntry, NewSalesEntryLine};
+use export::parse_export;
+use regex::Regex;
+use std::collections::HashMap;
+use tokio::fs;
+use tracing::info;
+
+pub mod export;
+
+pub struct Koala;
+
+impl Mode for Koala {
+    type Args = KoalaArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        program_args: &ProgramArgs,
+        config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        let exact_client = &external_clients.exact;
+        let koala_config = config
+            .koala
+            .as_ref()
+            .ok_or(Error::msg("No Koala configuration found"))?;
+
+        let period = booking_period(&args.booking, config)?;
+
+        // Check and prepare the Pretix lines first,
+        // so a Pretix event that can not be booked doesn't leave a Koala entry behind
+        let pretix = if args.with_pretix {
+            Some(
+                prepare_pretix(
+                    &args.booking,
+                    &period,
+                    program_args,
+                    config,
+                    external_clients,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+
+        // Parse the export
+        info!("Reading Koala export {}", args.file.display());
+        let contents = fs::read_to_string(&args.file).await?;
+        let sales = parse_export(&contents, &koala_config.columns)?;
+
+        let products = koala_config
+            .products
+            .iter()
+            .map(|product| Ok::<_, Error>((Regex::new(product.pattern.as_ref())?, product)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Map each product to its GL account, cost center and VAT code
+        let mut gl_accounts = HashMap::new();
+        let mut lines = Vec::new();
+        for sale in &sales {
+            let (_, product) = products
+                .iter()
+                .find(|(pattern, _)| pattern.is_match(&sale.product))
+                .ok_or(Error::msg(format!(
+                    "No Koala product configuration matches product '{}'",
+                    sale.product
+                )))?;
+
+            if !gl_accounts.contains_key(&product.gl_account) {
+                let gl_account = get_gl_account_by_code(exact_client, &product.gl_account).await?;
+                gl_accounts.insert(product.gl_account.clone(), gl_account);
+            }
+
+            let description = format!("Koala {period} | {}", sale.product);
+            info!(
+                "Creating sale line in Exact: {} {description} {} €{:.2}",
+                product.gl_account,
+                product
+                    .cost_center
+                    .as_ref()
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+                sale.amount
+            );
+
+            lines.push(NewSalesEntryLine {
+                entry_id: None,
+                gl_account: gl_accounts[&product.gl_account].clone(),
+                amount_fc: sale.amount,
+                vat_code: product.vat_code.clone(),
+                vat_amount_fc: None,
+                cost_center: product.cost_center.as_ref().map(|c| c.to_string()),
+                cost_unit: None,
+                description,
+            });
+        }
+
+        let customer = get_account_by_code(exact_client, &koala_config.customer).await?;
+        let entry = NewSalesEntry {
+            journal: config.exact.journals.sales.clone(),
+            customer,
+            entry_date: args.entry_date.unwrap_or(period.end),
+            description: args
+                .description
+                .clone()
+                .unwrap_or_else(|| format!("Koala {period}")),
+            lines,
+        };
+
+        let sales_entry = if program_args.dry_run {
+            info!(
+                "Flag '--dry-run' set. Not creating sales entry '{}'",
+                entry.description
+            );
+            None
+        } else {
+            let created = create_sales_entry(exact_client, &entry).await?;
+            info!("Created sales entry {}", created.entry_number);
+            Some(SalesEntryTarget::Existing(SalesEntryRef {
+                number: created.entry_number,
+                id: created.entry_id,
+            }))
+        };
+
+        if let Some(pretix) = pretix {
+            pretix
+                .book(
+                    &period,
+                    sales_entry.as_ref(),
+                    program_args,
+                    config,
+                    exact_client,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::args::OutputFormat;
+use color_eyre::Result;
+use serde::Serialize;
+
+/// Print values as a table or as JSON.
+/// `row` formats a value as the cells of a table row, in the order of `headers`.
+///
+/// # Errors
+///
+/// If the values could not be serialized
+pub fn print_list<T, F>(format: OutputFormat, headers: &[&str], values: &[T], row: F) -> Result<()>
+where
+    T: Serialize,
+    F: Fn(&T) -> Vec<String>,
+{
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(values)?),
+        OutputFormat::Table => print!(
+            "{}",
+            format_table(headers, &values.iter().map(row).collect::<Vec<_>>())
+        ),
+    }
+
+    Ok(())
+}
+
+/// Format rows as a table with aligned columns.
+fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
+
+    let mut table = format_row(headers.to_vec());
+    for row in rows {
+        table.push_str(&format_row(row.iter().map(String::as_str).collect()));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aligned_table() {
+        let table = format_table(
+            &["Code", "Description"],
+            &[
+                vec!["1302".to_string(), "Nog te ontvangen".to_string()],
+                vec!["80000".to_string(), "Omzet".to_string()],
+            ],
+        );
+
+        assert_eq!(
+            "Code   Description\n1302   Nog te ontvangen\n80000  Omzet\n",
+            table
+        );
+    }
+}
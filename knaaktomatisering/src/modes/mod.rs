@@ -2,8 +2,16 @@ use crate::args::ProgramArgs;
 use crate::config::Config;
 use color_eyre::Result;
 use exact_request::ExactClient;
+use mollie_request::MollieClient;
 use pretix_request::PretixClient;
 
+pub mod exact;
+pub mod history;
+pub mod init_event;
+pub mod koala;
+pub mod list;
+pub mod mollie;
+pub mod pretix;
 pub mod weekelijkse_plezier;
 
 pub trait Mode {
@@ -20,4 +28,6 @@ pub trait Mode {
 pub struct ExternalClients {
     pub exact: ExactClient,
     pub pretix: PretixClient,
+    /// Only available if Mollie is configured.
+    pub mollie: Option<MollieClient>,
 }
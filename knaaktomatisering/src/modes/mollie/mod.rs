@@ -0,0 +1,217 @@
+use crate::args::{MollieArgs, ProgramArgs};
+use crate::config::Config;
+use crate::ledger::Ledger;
+use crate::modes::weekelijkse_plezier::time_util::{timezone, DEFAULT_TIMEZONE};
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use http::StatusCode;
+use mollie_request::payments::{Payment, PaymentStatus};
+use mollie_request::settlements::{Settlement, SettlementStatus};
+use pretix_request::data_exporter::DateAxis;
+use pretix_request::orders::Order;
+use pretix_request::PretixClient;
+use reconcile::{journal_lines, ClearingAccounts, MatchedPayment, PretixPaymentMetadata};
+use time_tz::OffsetDateTimeExt;
+use tracing::{info, warn};
+
+pub mod reconcile;
+
+pub struct Mollie;
+
+impl Mode for Mollie {
+    type Args = MollieArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        program_args: &ProgramArgs,
+        config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        let mollie_client = external_clients
+            .mollie
+            .as_ref()
+            .ok_or(Error::msg("No Mollie configuration found"))?;
+        let journal = config
+            .exact
+            .journals
+            .general
+            .as_ref()
+            .ok_or(Error::msg("No general journal configured"))?;
+        let tz = timezone(config.timezone.as_deref().unwrap_or(DEFAULT_TIMEZONE))?;
+        let ledger = Ledger::read(Ledger::path(program_args, config)).await?;
+
+        info!("Fetching Mollie settlement {}", args.settlement);
+        let settlement = Settlement::get(mollie_client, &args.settlement).await?;
+        let reference = settlement
+            .reference
+            .clone()
+            .unwrap_or_else(|| settlement.id.to_string());
+
+        if settlement.status != SettlementStatus::Paidout {
+            warn!(
+                "Settlement {reference} has not been paid out yet ({:?})",
+                settlement.status
+            );
+        }
+
+        let payments = settlement.payments(mollie_client).await?;
+        let costs = settlement
+            .costs()
+            .map(|cost| cost.amount_gross.value)
+            .sum::<f32>();
+        info!(
+            "Settlement {reference}: {} with {} payments and €{costs:.2} costs",
+            settlement.amount,
+            payments.len()
+        );
+
+        // Match the Mollie payments to the Pretix payments
+        let mut matched = Vec::new();
+        let mut unmatched = 0.0;
+        for payment in &payments {
+            match match_payment(&external_clients.pretix, payment).await? {
+                Some(matched_payment) => matched.push(matched_payment),
+                None => unmatched += payment.amount.value,
+            }
+        }
+
+        info!(
+            "Matched {} of {} payments to Pretix",
+            matched.len(),
+            payments.len()
+        );
+
+        // The revenue of the payments should have been booked already
+        for payment in &matched {
+            let Some(paid_at) = payment.paid_at else {
+                continue;
+            };
+
+            // A payment is booked in the period of its payment date
+            let date = paid_at.to_timezone(tz).date();
+            if ledger
+                .overlapping(
+                    &payment.event.to_string(),
+                    date,
+                    date,
+                    DateAxis::LastPaymentDate,
+                )
+                .is_empty()
+            {
+                warn!(
+                    "Payment {} for order {}/{} on {date} has not been booked yet",
+                    payment.payment, payment.event, payment.order
+                );
+            }
+        }
+
+        // The revenue was booked on the receivable of the Pretix customer
+        let receivables = config
+            .exact
+            .gl_accounts
+            .receivables
+            .as_ref()
+            .ok_or(Error::msg("No receivables GL account configured"))?;
+        let customer = config
+            .pretix
+            .customer
+            .as_deref()
+            .ok_or(Error::msg("No Pretix customer configured"))?;
+
+        let lines = journal_lines(
+            &reference,
+            &matched,
+            costs,
+            &ClearingAccounts {
+                receivables,
+                customer,
+                unassigned: &config.exact.gl_accounts.unassigned_payments,
+                costs: &config.exact.gl_accounts.bookkeeping,
+            },
+        )?;
+
+        println!("Proposed entry in general journal {journal}:");
+        for line in &lines {
+            println!("{line}");
+        }
+
+        if unmatched != 0.0 {
+            warn!(
+                "€{unmatched:.2} of unmatched payments stays on {}, the booked costs include theirs",
+                config.exact.gl_accounts.unassigned_payments
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Match a Mollie payment to the Pretix payment it was created for.
+/// Returns `None` if the payment is not a Pretix payment,
+/// or if it does not match the payment in Pretix.
+///
+/// # Errors
+///
+/// If the request to Pretix fails
+async fn match_payment(
+    pretix_client: &PretixClient,
+    payment: &Payment,
+) -> Result<Option<MatchedPayment>> {
+    if payment.status != PaymentStatus::Paid {
+        warn!(
+            "Payment {} has status {:?}, skipping",
+            payment.id, payment.status
+        );
+        return Ok(None);
+    }
+
+    let Some(metadata) = payment.metadata::<PretixPaymentMetadata>() else {
+        warn!(
+            "Payment {} '{}' was not made through Pretix",
+            payment.id, payment.description
+        );
+        return Ok(None);
+    };
+
+    let order = match Order::get(
+        pretix_client,
+        &metadata.organizer(),
+        &metadata.event(),
+        &metadata.order,
+    )
+    .await
+    {
+        Ok(order) => order,
+        Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => {
+            warn!(
+                "Payment {}: order {}/{} does not exist in Pretix",
+                payment.id, metadata.event, metadata.order
+            );
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let matches = order.payments.iter().any(|order_payment| {
+        order_payment.local_id == metadata.payment
+            && order_payment.is_successful()
+            && (order_payment.amount - payment.amount.value).abs() < 0.005
+    });
+
+    if !matches {
+        warn!(
+            "Payment {} of {} does not match a payment of order {}/{} in Pretix",
+            payment.id, payment.amount, metadata.event, metadata.order
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(MatchedPayment {
+        payment: payment.id.clone(),
+        event: metadata.event(),
+        order: metadata.order,
+        amount: payment.amount.value,
+        paid_at: payment.paid_at,
+    }))
+}
@@ -0,0 +1,270 @@
+use crate::config::ExactGLAccountCode;
+use crate::modes::weekelijkse_plezier::rounding::{from_cents, to_cents};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use mollie_request::payments::PaymentId;
+use pretix_request::events::EventId;
+use pretix_request::organizer::OrganizerId;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use time::OffsetDateTime;
+
+/// The metadata Pretix adds to the Mollie payments it creates.
+#[derive(Debug, Deserialize)]
+pub struct PretixPaymentMetadata {
+    pub organizer: String,
+    pub event: String,
+    /// The order code
+    pub order: String,
+    /// The ID of the payment within the order
+    pub payment: u32,
+}
+
+impl PretixPaymentMetadata {
+    pub fn organizer(&self) -> OrganizerId {
+        OrganizerId(self.organizer.clone())
+    }
+
+    pub fn event(&self) -> EventId {
+        EventId(self.event.clone())
+    }
+}
+
+/// A Mollie payment matched to a Pretix payment.
+#[derive(Debug)]
+pub struct MatchedPayment {
+    pub payment: PaymentId,
+    pub event: EventId,
+    pub order: String,
+    pub amount: f32,
+    pub paid_at: Option<OffsetDateTime>,
+}
+
+/// A line of the proposed general journal entry.
+#[derive(Debug)]
+pub struct JournalLine {
+    pub gl_account_code: ExactGLAccountCode,
+    /// The code of the customer, for lines on the receivables account
+    pub account: Option<String>,
+    pub description: String,
+    /// Positive amounts are debited, negative amounts are credited.
+    pub amount: f32,
+}
+
+/// The accounts the settlement is cleared against.
+pub struct ClearingAccounts<'a> {
+    /// The receivables GL account the Pretix revenue is booked on
+    pub receivables: &'a ExactGLAccountCode,
+    /// The customer the sales entries for Pretix revenue are booked on
+    pub customer: &'a str,
+    /// The GL account the settlement was received on
+    pub unassigned: &'a ExactGLAccountCode,
+    /// The GL account for the costs of the settlement
+    pub costs: &'a ExactGLAccountCode,
+}
+
+/// Propose the general journal entry clearing the unassigned payments of a settlement.
+///
+/// The revenue was booked through sales entries on the receivable of the Pretix customer,
+/// so the matched payments settle that receivable, with one line per event.
+/// The costs of the settlement are booked in full, Mollie does not split them per payment.
+/// As they also cover payments that could not be matched, they are cleared from the
+/// unassigned payments on a line of their own. Unmatched payments stay on the unassigned payments.
+///
+/// # Errors
+///
+/// If the entry does not balance
+pub fn journal_lines(
+    reference: &str,
+    matched: &[MatchedPayment],
+    costs: f32,
+    accounts: &ClearingAccounts,
+) -> Result<Vec<JournalLine>> {
+    let mut revenue_per_event = BTreeMap::<&EventId, i64>::new();
+    for payment in matched {
+        *revenue_per_event.entry(&payment.event).or_default() += to_cents(payment.amount);
+    }
+
+    let line_name = format!("Mollie {reference}");
+    let mut lines = Vec::new();
+
+    let mut revenue = 0;
+    for (event, amount) in revenue_per_event {
+        revenue += amount;
+
+        lines.push(JournalLine {
+            gl_account_code: accounts.receivables.clone(),
+            account: Some(accounts.customer.to_string()),
+            description: format!("{line_name} | Pretix {event}"),
+            amount: from_cents(-amount),
+        });
+    }
+
+    if !matched.is_empty() {
+        lines.push(JournalLine {
+            gl_account_code: accounts.unassigned.clone(),
+            account: None,
+            description: format!("{line_name} | Pretix"),
+            amount: from_cents(revenue),
+        });
+    }
+
+    let costs = to_cents(costs);
+    lines.push(JournalLine {
+        gl_account_code: accounts.costs.clone(),
+        account: None,
+        description: format!("{line_name} | Transactiekosten"),
+        amount: from_cents(costs),
+    });
+    lines.push(JournalLine {
+        gl_account_code: accounts.unassigned.clone(),
+        account: None,
+        description: format!("{line_name} | Transactiekosten"),
+        amount: from_cents(-costs),
+    });
+
+    let balance = lines.iter().map(|line| to_cents(line.amount)).sum::<i64>();
+    if balance != 0 {
+        return Err(Error::msg(format!(
+            "The journal entry for settlement {reference} does not balance: {:.2}",
+            from_cents(balance)
+        )));
+    }
+
+    Ok(lines)
+}
+
+impl Display for JournalLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let side = if self.amount < 0.0 { "Credit" } else { "Debit" };
+        write!(f, "{}", self.gl_account_code)?;
+        if let Some(account) = &self.account {
+            write!(f, " ({account})")?;
+        }
+        write!(f, " {} {side} €{:.2}", self.description, self.amount.abs())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn payment(event: &str, amount: f32) -> MatchedPayment {
+        MatchedPayment {
+            payment: PaymentId("tr_test".to_string()),
+            event: EventId(event.to_string()),
+            order: "ABC12".to_string(),
+            amount,
+            paid_at: None,
+        }
+    }
+
+    fn accounts() -> (ExactGLAccountCode, ExactGLAccountCode, ExactGLAccountCode) {
+        (
+            ExactGLAccountCode("1300".to_string()),
+            ExactGLAccountCode("1302".to_string()),
+            ExactGLAccountCode("5007".to_string()),
+        )
+    }
+
+    #[test]
+    fn pretix_metadata() {
+        let payment: PretixPaymentMetadata = serde_json::from_str(
+            r#"{"organizer": "sticky", "event": "intro", "order": "ABC12", "payment": 1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(EventId("intro".to_string()), payment.event());
+        assert_eq!(1, payment.payment);
+    }
+
+    #[test]
+    fn journal_lines_balance() {
+        let matched = [
+            payment("intro", 25.0),
+            payment("intro", 10.10),
+            payment("gala", 4.9),
+        ];
+        let (receivables, unassigned, costs) = accounts();
+
+        let lines = journal_lines(
+            "1234567.2410.03",
+            &matched,
+            0.58,
+            &ClearingAccounts {
+                receivables: &receivables,
+                customer: "PRETIX",
+                unassigned: &unassigned,
+                costs: &costs,
+            },
+        )
+        .unwrap();
+
+        let amounts = lines
+            .iter()
+            .map(|line| (line.gl_account_code.0.as_str(), line.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("1300", -4.9),
+                ("1300", -35.1),
+                ("1302", 40.0),
+                ("5007", 0.58),
+                ("1302", -0.58)
+            ],
+            amounts
+        );
+        assert!(lines
+            .iter()
+            .filter(|line| line.gl_account_code == receivables)
+            .all(|line| line.account.as_deref() == Some("PRETIX")));
+    }
+
+    #[test]
+    fn many_payments_summed_in_cents() {
+        let matched = (0..1000).map(|_| payment("intro", 0.1)).collect::<Vec<_>>();
+        let (receivables, unassigned, costs) = accounts();
+
+        let lines = journal_lines(
+            "1234567.2410.03",
+            &matched,
+            0.0,
+            &ClearingAccounts {
+                receivables: &receivables,
+                customer: "PRETIX",
+                unassigned: &unassigned,
+                costs: &costs,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(-100.0, lines[0].amount);
+        assert_eq!(100.0, lines[1].amount);
+    }
+
+    #[test]
+    fn costs_without_matched_payments() {
+        let (receivables, unassigned, costs) = accounts();
+
+        let lines = journal_lines(
+            "1234567.2410.03",
+            &[],
+            0.29,
+            &ClearingAccounts {
+                receivables: &receivables,
+                customer: "PRETIX",
+                unassigned: &unassigned,
+                costs: &costs,
+            },
+        )
+        .unwrap();
+
+        // Only the costs are cleared, the unmatched payments stay on the unassigned payments
+        let amounts = lines
+            .iter()
+            .map(|line| (line.gl_account_code.0.as_str(), line.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("5007", 0.29), ("1302", -0.29)], amounts);
+    }
+}
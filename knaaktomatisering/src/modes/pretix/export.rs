@@ -0,0 +1,76 @@
+use crate::args::{PretixExportArgs, ProgramArgs};
+use crate::config::Config;
+use crate::modes::pretix::resolve_organizer;
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::Result;
+use pretix_request::data_exporter::{DataExporter, ExportParameterValue, ExportParameters};
+use pretix_request::events::EventId;
+use tracing::info;
+
+pub struct PretixExport;
+
+impl Mode for PretixExport {
+    type Args = PretixExportArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        _program_args: &ProgramArgs,
+        _config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        let pretix_client = &external_clients.pretix;
+
+        let event = EventId(args.event.clone());
+        let organizer = resolve_organizer(pretix_client, args.organizer.as_deref(), &event).await?;
+
+        let parameters = export_parameters(&args.params);
+
+        info!("Running exporter {organizer}/{event}/{}", args.exporter);
+        let url = DataExporter::run(
+            pretix_client,
+            &organizer,
+            &event,
+            &args.exporter,
+            &parameters,
+        )
+        .await?;
+
+        let written = DataExporter::download_to_file(pretix_client, url, &args.output).await?;
+        info!(
+            "Export complete. Wrote {written} bytes to {}",
+            args.output.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Turn the `key=value` pairs from the command line into exporter parameters.
+/// Keys that occur more than once become a list,
+/// the values `true` and `false` become booleans.
+fn export_parameters(params: &[(String, String)]) -> ExportParameters {
+    let mut parameters = ExportParameters::new();
+    for (key, value) in params {
+        let new_value = match parameters.remove(key) {
+            None => match value.as_str() {
+                "true" => ExportParameterValue::Bool(true),
+                "false" => ExportParameterValue::Bool(false),
+                _ => ExportParameterValue::String(value.clone()),
+            },
+            Some(ExportParameterValue::List(mut list)) => {
+                list.push(value.clone());
+                ExportParameterValue::List(list)
+            }
+            Some(ExportParameterValue::String(previous)) => {
+                ExportParameterValue::List(vec![previous, value.clone()])
+            }
+            Some(ExportParameterValue::Bool(previous)) => {
+                ExportParameterValue::List(vec![previous.to_string(), value.clone()])
+            }
+        };
+
+        parameters.insert(key.clone(), new_value);
+    }
+
+    parameters
+}
@@ -0,0 +1,108 @@
+use crate::args::{PretixListArgs, PretixListResource, ProgramArgs};
+use crate::config::Config;
+use crate::modes::list::print_list;
+use crate::modes::pretix::{localized, resolve_organizer};
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::Result;
+use pretix_request::data_exporter::DataExporter;
+use pretix_request::events::{Event, EventId};
+use pretix_request::items::Item;
+use pretix_request::organizer::{Organizer, OrganizerId};
+
+pub struct PretixList;
+
+impl Mode for PretixList {
+    type Args = PretixListArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        _program_args: &ProgramArgs,
+        _config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        let pretix_client = &external_clients.pretix;
+
+        match &args.resource {
+            PretixListResource::Organizers => {
+                let organizers = Organizer::list(pretix_client).await?;
+                print_list(args.format, &["Slug", "Name"], &organizers, |o| {
+                    vec![o.slug.to_string(), o.name.clone()]
+                })
+            }
+            PretixListResource::Events { organizer } => {
+                let organizers = match organizer {
+                    Some(organizer) => vec![OrganizerId(organizer.clone())],
+                    None => Organizer::list(pretix_client)
+                        .await?
+                        .into_iter()
+                        .map(|o| o.slug)
+                        .collect(),
+                };
+
+                let mut events = Vec::new();
+                for organizer in organizers {
+                    events.extend(Event::list(pretix_client, &organizer).await?);
+                }
+
+                print_list(
+                    args.format,
+                    &["Slug", "Name", "Live", "From", "To"],
+                    &events,
+                    |e| {
+                        vec![
+                            e.slug.to_string(),
+                            localized(&e.name),
+                            e.live.to_string(),
+                            e.date_from.map(|d| d.to_string()).unwrap_or_default(),
+                            e.date_to.map(|d| d.to_string()).unwrap_or_default(),
+                        ]
+                    },
+                )
+            }
+            PretixListResource::Items { event, organizer } => {
+                let event = EventId(event.clone());
+                let organizer =
+                    resolve_organizer(pretix_client, organizer.as_deref(), &event).await?;
+                let items = Item::list(pretix_client, &organizer, &event).await?;
+
+                print_list(
+                    args.format,
+                    &["ID", "Name", "Active", "Price", "Tax rate"],
+                    &items,
+                    |i| {
+                        vec![
+                            i.id.to_string(),
+                            localized(&i.name),
+                            i.active.to_string(),
+                            format!("{:.2}", i.default_price),
+                            format!("{:.2}%", i.tax_rate),
+                        ]
+                    },
+                )
+            }
+            PretixListResource::Exporters { event, organizer } => {
+                let event = EventId(event.clone());
+                let organizer =
+                    resolve_organizer(pretix_client, organizer.as_deref(), &event).await?;
+                let exporters = DataExporter::list(pretix_client, &organizer, &event).await?;
+
+                print_list(
+                    args.format,
+                    &["Identifier", "Name", "Parameters"],
+                    &exporters,
+                    |e| {
+                        vec![
+                            e.identifier.clone(),
+                            e.verbose_name.clone(),
+                            e.input_parameters
+                                .iter()
+                                .map(|p| p.name.clone())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ]
+                    },
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::args::{PretixArgs, PretixCommand, ProgramArgs};
+use crate::config::Config;
+use crate::modes::{ExternalClients, Mode};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use pretix_request::events::{Event, EventId};
+use pretix_request::organizer::{Organizer, OrganizerId};
+use pretix_request::PretixClient;
+use std::collections::HashMap;
+
+pub mod export;
+pub mod list;
+
+pub struct Pretix;
+
+impl Mode for Pretix {
+    type Args = PretixArgs;
+
+    async fn execute_mode(
+        args: &Self::Args,
+        program_args: &ProgramArgs,
+        config: &Config,
+        external_clients: &ExternalClients,
+    ) -> Result<()> {
+        match &args.command {
+            PretixCommand::Export(args) => {
+                export::PretixExport::execute_mode(args, program_args, config, external_clients)
+                    .await
+            }
+            PretixCommand::List(args) => {
+                list::PretixList::execute_mode(args, program_args, config, external_clients).await
+            }
+        }
+    }
+}
+
+/// Find the organizer of an event.
+/// If `organizer` is provided, it is returned as-is.
+/// Otherwise, the events of all accessible organizers are searched.
+///
+/// # Errors
+///
+/// - If the event does not exist in any organizer
+/// - If the event exists in more than one organizer
+/// - If the request fails
+pub async fn resolve_organizer(
+    client: &PretixClient,
+    organizer: Option<&str>,
+    event: &EventId,
+) -> Result<OrganizerId> {
+    if let Some(organizer) = organizer {
+        return Ok(OrganizerId(organizer.to_string()));
+    }
+
+    let mut candidates = Vec::new();
+    for organizer in Organizer::list(client).await? {
+        let events = Event::list(client, &organizer.slug).await?;
+        if events.iter().any(|e| e.slug.eq(event)) {
+            candidates.push(organizer.slug);
+        }
+    }
+
+    match candidates.len() {
+        0 => Err(Error::msg(format!(
+            "Event {event} does not exist in any accessible organizer"
+        ))),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(Error::msg(format!(
+            "Event {event} exists in multiple organizers ({}). Specify one with '--organizer'",
+            candidates
+                .iter()
+                .map(|o| o.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// The English name, or any name if there is no English one.
+pub fn localized(name: &HashMap<String, String>) -> String {
+    name.get("en")
+        .or(name.values().next())
+        .cloned()
+        .unwrap_or_default()
+}
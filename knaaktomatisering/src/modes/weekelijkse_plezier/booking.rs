@@ -0,0 +1,78 @@
+use crate::config::{ExactCostCenterCode, ExactCostUnitCode, ExactGLAccountCode};
+use crate::ledger::LedgerLine;
+use exact_filter::Guid;
+use exact_request::api::sales_entry::NewSalesEntryLine;
+use std::fmt::Display;
+
+/// A line to be added to the sales entry in Exact.
+#[derive(Debug)]
+pub struct SaleLine {
+    pub description: String,
+    pub gl_account_code: ExactGLAccountCode,
+    pub gl_account: Guid,
+    pub cost_center: Option<ExactCostCenterCode>,
+    pub cost_unit: Option<ExactCostUnitCode>,
+    pub vat_code: Option<String>,
+    /// The value excluding VAT
+    pub amount: f32,
+    /// The VAT on top of `amount`, as computed by Pretix.
+    /// If `None`, Exact computes it from the VAT code
+    pub vat: Option<f32>,
+}
+
+impl SaleLine {
+    /// The line to create in Exact.
+    /// `entry_id` must be `None` when the line is part of a new sales entry.
+    pub fn new_line(&self, entry_id: Option<Guid>) -> NewSalesEntryLine {
+        NewSalesEntryLine {
+            entry_id,
+            gl_account: self.gl_account.clone(),
+            amount_fc: self.amount,
+            vat_code: self.vat_code.clone(),
+            vat_amount_fc: self.vat,
+            cost_center: self.cost_center.as_ref().map(|c| c.to_string()),
+            cost_unit: self.cost_unit.as_ref().map(|c| c.to_string()),
+            description: self.description.clone(),
+        }
+    }
+
+    /// Whether the line is booked on the same accounts, with the same description, as `other`.
+    pub fn books_like(&self, other: &SaleLine) -> bool {
+        self.description == other.description
+            && self.gl_account_code == other.gl_account_code
+            && self.cost_center == other.cost_center
+            && self.cost_unit == other.cost_unit
+            && self.vat_code == other.vat_code
+    }
+
+    /// The line as recorded in the ledger.
+    pub fn ledger_line(&self) -> LedgerLine {
+        LedgerLine {
+            description: self.description.clone(),
+            gl_account: self.gl_account_code.to_string(),
+            amount: self.amount,
+            vat: self.vat.unwrap_or_default(),
+        }
+    }
+}
+
+impl Display for SaleLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.gl_account_code, self.description)?;
+        if let Some(cost_center) = &self.cost_center {
+            write!(f, " {cost_center}")?;
+        }
+        if let Some(cost_unit) = &self.cost_unit {
+            write!(f, " {cost_unit}")?;
+        }
+        if let Some(vat_code) = &self.vat_code {
+            write!(f, " VAT {vat_code}")?;
+        }
+        write!(f, " €{:.2}", self.amount)?;
+        if let Some(vat) = self.vat {
+            write!(f, " + VAT €{vat:.2}")?;
+        }
+
+        Ok(())
+    }
+}
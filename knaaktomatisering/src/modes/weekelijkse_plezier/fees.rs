@@ -0,0 +1,370 @@
+use crate::config::{ExactCostCenterCode, ExactGLAccountCode, FeeAllocation, FeeTarget};
+use crate::modes::weekelijkse_plezier::booking::SaleLine;
+use crate::modes::weekelijkse_plezier::rounding::{allocate, from_cents, to_cents};
+use std::collections::HashMap;
+
+/// A line for (a part of) the transaction fees of an event.
+#[derive(Debug, PartialEq)]
+pub struct FeeLine {
+    pub description: String,
+    pub gl_account: ExactGLAccountCode,
+    pub cost_center: Option<ExactCostCenterCode>,
+    /// The value excluding VAT
+    pub amount: f32,
+    /// The VAT on top of `amount`
+    pub vat: f32,
+}
+
+/// Divide the fees of an event into lines, as configured by `allocation`.
+/// The lines are excluding VAT. Their amounts plus VAT add up to `fees`,
+/// and their VAT to `fees_vat`, to the cent.
+///
+/// `product_lines` are the revenue lines of the event, used for the pro-rata allocation.
+/// `line_name` is the general name of the event's lines, e.g. `Pretix Intro | week 42`.
+pub fn allocate_fees(
+    allocation: &FeeAllocation,
+    fees: f32,
+    fees_vat: f32,
+    fees_per_provider: &HashMap<String, f32>,
+    product_lines: &[SaleLine],
+    bookkeeping: &ExactGLAccountCode,
+    line_name: &str,
+) -> Vec<FeeLine> {
+    let mut lines = gross_fee_lines(
+        allocation,
+        fees,
+        fees_per_provider,
+        product_lines,
+        bookkeeping,
+        line_name,
+    );
+
+    // The VAT is divided in proportion to the gross amounts
+    let weights = lines
+        .iter()
+        .map(|line| line.amount as f64)
+        .collect::<Vec<_>>();
+    if let Some(shares) = allocate(to_cents(fees_vat), &weights) {
+        for (line, vat) in lines.iter_mut().zip(shares) {
+            line.amount = from_cents(to_cents(line.amount) - vat);
+            line.vat = from_cents(vat);
+        }
+    }
+
+    lines
+}
+
+/// Divide the fees into lines including VAT.
+/// Lines of zero cents are left out.
+fn gross_fee_lines(
+    allocation: &FeeAllocation,
+    fees: f32,
+    fees_per_provider: &HashMap<String, f32>,
+    product_lines: &[SaleLine],
+    bookkeeping: &ExactGLAccountCode,
+    line_name: &str,
+) -> Vec<FeeLine> {
+    let trx_line_name = format!("{line_name} | Transactiekosten");
+    let line = |description: String, target: &FeeTarget, cents: i64| FeeLine {
+        description,
+        gl_account: target.gl_account.clone().unwrap_or(bookkeeping.clone()),
+        cost_center: target.cost_center.clone(),
+        amount: from_cents(cents),
+        vat: 0.0,
+    };
+
+    let single = |target: &FeeTarget| match to_cents(fees) {
+        0 => Vec::new(),
+        cents => vec![line(trx_line_name.clone(), target, cents)],
+    };
+
+    match allocation {
+        FeeAllocation::Single { target } => single(target),
+        FeeAllocation::ProRata { target } => {
+            let weights = product_lines
+                .iter()
+                .map(|line| line.amount as f64)
+                .collect::<Vec<_>>();
+            let Some(shares) = allocate(to_cents(fees), &weights) else {
+                return single(target);
+            };
+
+            let mut lines: Vec<FeeLine> = Vec::new();
+            for (product_line, cents) in product_lines.iter().zip(shares) {
+                let target = FeeTarget {
+                    gl_account: target.gl_account.clone(),
+                    cost_center: target
+                        .cost_center
+                        .clone()
+                        .or(product_line.cost_center.clone()),
+                };
+                let share = line(trx_line_name.clone(), &target, cents);
+
+                // Shares on the same cost center are combined
+                match lines.iter_mut().find(|other| {
+                    other.gl_account == share.gl_account && other.cost_center == share.cost_center
+                }) {
+                    Some(other) => other.amount = from_cents(to_cents(other.amount) + cents),
+                    None => lines.push(share),
+                }
+            }
+
+            // Shares may round to zero
+            lines.retain(|line| to_cents(line.amount) != 0);
+            lines
+        }
+        FeeAllocation::PerProvider { target, providers } => {
+            let mut per_provider = fees_per_provider.iter().collect::<Vec<_>>();
+            per_provider.sort_by_key(|(provider, _)| *provider);
+
+            let weights = per_provider
+                .iter()
+                .map(|(_, fees)| **fees as f64)
+                .collect::<Vec<_>>();
+            let Some(shares) = allocate(to_cents(fees), &weights) else {
+                return single(target);
+            };
+
+            per_provider
+                .into_iter()
+                .zip(shares)
+                .filter(|(_, cents)| *cents != 0)
+                .map(|((provider, _), cents)| {
+                    let provider_target = providers.get(provider);
+                    let target = FeeTarget {
+                        gl_account: provider_target
+                            .and_then(|t| t.gl_account.clone())
+                            .or(target.gl_account.clone()),
+                        cost_center: provider_target
+                            .and_then(|t| t.cost_center.clone())
+                            .or(target.cost_center.clone()),
+                    };
+                    line(format!("{trx_line_name} | {provider}"), &target, cents)
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use exact_filter::Guid;
+
+    fn product_line(cost_center: &str, amount: f32) -> SaleLine {
+        SaleLine {
+            description: "Pretix Intro | week 42 | Ticket".to_string(),
+            gl_account_code: ExactGLAccountCode("8000".to_string()),
+            gl_account: Guid::new("00000000-0000-0000-0000-000000000000"),
+            cost_center: Some(ExactCostCenterCode(cost_center.to_string())),
+            cost_unit: None,
+            vat_code: Some("2".to_string()),
+            amount,
+            vat: None,
+        }
+    }
+
+    fn bookkeeping() -> ExactGLAccountCode {
+        ExactGLAccountCode("5007".to_string())
+    }
+
+    #[test]
+    fn pro_rata_per_cost_center() {
+        let allocation: FeeAllocation =
+            serde_json::from_str(r#"{"strategy": "pro_rata", "gl_account": "4500"}"#).unwrap();
+        let product_lines = [
+            product_line("INTRO", 10.0),
+            product_line("MERCH", 20.0),
+            product_line("INTRO", 10.0),
+        ];
+
+        let lines = allocate_fees(
+            &allocation,
+            1.0,
+            0.0,
+            &HashMap::new(),
+            &product_lines,
+            &bookkeeping(),
+            "Pretix Intro | week 42",
+        );
+
+        assert_eq!(2, lines.len());
+        assert_eq!("4500", lines[0].gl_account.0);
+        assert_eq!(
+            Some("INTRO"),
+            lines[0].cost_center.as_ref().map(|c| c.0.as_str())
+        );
+        assert_eq!(0.5, lines[0].amount);
+        assert_eq!(
+            Some("MERCH"),
+            lines[1].cost_center.as_ref().map(|c| c.0.as_str())
+        );
+        assert_eq!(0.5, lines[1].amount);
+    }
+
+    #[test]
+    fn pro_rata_cents_add_up() {
+        let allocation: FeeAllocation =
+            serde_json::from_str(r#"{"strategy": "pro_rata"}"#).unwrap();
+        let product_lines = [
+            product_line("A", 10.0),
+            product_line("B", 10.0),
+            product_line("C", 10.0),
+        ];
+
+        let lines = allocate_fees(
+            &allocation,
+            0.29,
+            0.0,
+            &HashMap::new(),
+            &product_lines,
+            &bookkeeping(),
+            "Pretix Intro | week 42",
+        );
+
+        let cents = lines
+            .iter()
+            .map(|line| to_cents(line.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![10, 10, 9], cents);
+        assert!(lines.iter().all(|line| line.gl_account == bookkeeping()));
+    }
+
+    #[test]
+    fn per_provider() {
+        let allocation: FeeAllocation = serde_json::from_str(
+            r#"{
+                "strategy": "per_provider",
+                "cost_center": "TRX",
+                "providers": { "mollie": { "gl_account": "4510" } }
+            }"#,
+        )
+        .unwrap();
+        let fees_per_provider = HashMap::from([
+            ("mollie".to_string(), 1.25),
+            ("banktransfer".to_string(), 0.5),
+        ]);
+
+        let lines = allocate_fees(
+            &allocation,
+            1.75,
+            0.0,
+            &fees_per_provider,
+            &[],
+            &bookkeeping(),
+            "Pretix Intro | week 42",
+        );
+
+        assert_eq!(
+            vec![
+                FeeLine {
+                    description: "Pretix Intro | week 42 | Transactiekosten | banktransfer"
+                        .to_string(),
+                    gl_account: bookkeeping(),
+                    cost_center: Some(ExactCostCenterCode("TRX".to_string())),
+                    amount: 0.5,
+                    vat: 0.0,
+                },
+                FeeLine {
+                    description: "Pretix Intro | week 42 | Transactiekosten | mollie".to_string(),
+                    gl_account: ExactGLAccountCode("4510".to_string()),
+                    cost_center: Some(ExactCostCenterCode("TRX".to_string())),
+                    amount: 1.25,
+                    vat: 0.0,
+                },
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn single_by_default() {
+        let lines = allocate_fees(
+            &FeeAllocation::default(),
+            0.5,
+            0.0,
+            &HashMap::new(),
+            &[],
+            &bookkeeping(),
+            "Pretix Intro | week 42",
+        );
+
+        assert_eq!(
+            vec![FeeLine {
+                description: "Pretix Intro | week 42 | Transactiekosten".to_string(),
+                gl_account: bookkeeping(),
+                cost_center: None,
+                amount: 0.5,
+                vat: 0.0,
+            }],
+            lines
+        );
+    }
+
+    #[test]
+    fn no_lines_without_fees() {
+        let pro_rata: FeeAllocation = serde_json::from_str(r#"{"strategy": "pro_rata"}"#).unwrap();
+        for allocation in [FeeAllocation::default(), pro_rata] {
+            let lines = allocate_fees(
+                &allocation,
+                0.0,
+                0.0,
+                &HashMap::new(),
+                &[product_line("INTRO", 10.0)],
+                &bookkeeping(),
+                "Pretix Intro | week 42",
+            );
+            assert!(lines.is_empty());
+        }
+    }
+
+    #[test]
+    fn pro_rata_shares_of_zero_cents_left_out() {
+        let allocation: FeeAllocation =
+            serde_json::from_str(r#"{"strategy": "pro_rata"}"#).unwrap();
+        let product_lines = [product_line("INTRO", 1000.0), product_line("MERCH", 1.0)];
+
+        let lines = allocate_fees(
+            &allocation,
+            0.01,
+            0.0,
+            &HashMap::new(),
+            &product_lines,
+            &bookkeeping(),
+            "Pretix Intro | week 42",
+        );
+
+        assert_eq!(1, lines.len());
+        assert_eq!(
+            Some("INTRO"),
+            lines[0].cost_center.as_ref().map(|c| c.0.as_str())
+        );
+        assert_eq!(0.01, lines[0].amount);
+    }
+
+    #[test]
+    fn vat_taken_out_per_line() {
+        let allocation: FeeAllocation =
+            serde_json::from_str(r#"{"strategy": "per_provider"}"#).unwrap();
+        let fees_per_provider = HashMap::from([
+            ("mollie".to_string(), 1.21),
+            ("banktransfer".to_string(), 2.42),
+        ]);
+
+        let lines = allocate_fees(
+            &allocation,
+            3.63,
+            0.63,
+            &fees_per_provider,
+            &[],
+            &bookkeeping(),
+            "Pretix Intro | week 42",
+        );
+
+        let amounts = lines
+            .iter()
+            .map(|line| (to_cents(line.amount), to_cents(line.vat)))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(200, 42), (100, 21)], amounts);
+    }
+}
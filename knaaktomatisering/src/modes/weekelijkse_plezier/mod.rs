@@ -1,22 +1,45 @@
-use crate::args::{ProgramArgs, WeekelijksePlezierArgs};
-use crate::config::{Config, PretixEventId};
+use crate::args::{PretixBookingArgs, ProgramArgs, WeekelijksePlezierArgs};
+use crate::config::{
+    Config, ExactGLAccountCode, FeeAllocation, PretixEventConfig, PretixEventId,
+    UnconfiguredEventPolicy,
+};
+use crate::ledger::{Ledger, LedgerRecord};
 use crate::modes::{ExternalClients, Mode};
+use booking::SaleLine;
 use color_eyre::eyre::Error;
 use color_eyre::Result;
-use exact_request::api::cost_center::get_cost_center_by_code;
+use exact_filter::Guid;
+use exact_request::api::account::get_account_by_code;
 use exact_request::api::gl_account::get_gl_account_by_code;
-use exact_request::api::sales_entry::{get_sales_entry_for_entry_number, get_sales_entry_lines};
-use futures_util::future::try_join_all;
-use pretix::pretix_totals;
+use exact_request::api::sales_entry::{
+    create_sales_entry, create_sales_entry_lines, get_sales_entry_for_entry_number,
+    get_sales_entry_lines, NewSalesEntry,
+};
+use exact_request::cache::CodeEntity;
+use exact_request::{ExactClient, ExactError};
+use fees::{allocate_fees, FeeLine};
+use period::{period_from_args, Period};
+use pretix::{live_events, pretix_totals, vat_per_rate, EventSummary};
+use pretix_request::data_exporter::DateAxis;
+use pretix_request::events::EventId;
 use regex::Regex;
-use std::collections::HashMap;
-use std::future::Future;
-use time::{Duration, UtcOffset};
-use time_util::last_monday;
-use tracing::info;
+use rounding::{from_cents, to_cents};
+use rules::Rules;
+use std::collections::BTreeMap;
+use time::{Date, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+use time_util::{timezone, DEFAULT_TIMEZONE};
+use tracing::{debug, info, warn};
+use vat::VatCodes;
 
+pub mod booking;
+pub mod fees;
+pub mod period;
 pub mod pretix;
+pub mod rounding;
+pub mod rules;
 pub mod time_util;
+pub mod vat;
 
 pub struct WeekelijksePlezier;
 
@@ -30,170 +53,833 @@ impl Mode for WeekelijksePlezier {
         external_clients: &ExternalClients,
     ) -> Result<()> {
         let exact_client = &external_clients.exact;
-        let pretix_client = &external_clients.pretix;
+        let period = booking_period(&args.booking, config)?;
 
-        if args.periods_ago == 0 {
-            return Err(Error::msg("Argument '--periods-ago' may not be 0. 0 would mean you're looking from the most recent monday up until the next sunday, which isn't possible, as that sunday either hasn't happened yet, or it is still sunday."));
-        }
-
-        // Fetch the sales entry to which we should import the pretix data.
+        // Fetch the sales entry to which we should import the pretix data,
+        // or the customer of the entry to create.
         // While we don't need the data until we're going to be importing the
         // pretix data, if this fails there's no point in running the pretix
         // exports, which are expensive.
-        info!("Fetching sales entry information from Exact");
-        let sales_entry =
-            get_sales_entry_for_entry_number(exact_client, args.transaction_id).await?;
-        let sales_entry_lines = get_sales_entry_lines(exact_client, &sales_entry).await?;
-        info!("{sales_entry_lines:?}");
+        let target = match args.transaction_id {
+            Some(number) => {
+                info!("Fetching sales entry information from Exact");
+                let sales_entry = SalesEntryRef {
+                    number,
+                    id: get_sales_entry_for_entry_number(exact_client, number).await?,
+                };
+                let sales_entry_lines =
+                    get_sales_entry_lines(exact_client, &sales_entry.id).await?;
+                debug!("{sales_entry_lines:?}");
 
-        // Timezone hell
-        let offset = UtcOffset::from_whole_seconds(args.utc_offset_hours * 3600)?;
+                SalesEntryTarget::Existing(sales_entry)
+            }
+            None => {
+                let customer = config.pretix.customer.as_ref().ok_or(Error::msg(
+                    "No customer configured for new sales entries. Configure one or use '--transaction-id'",
+                ))?;
 
-        // Calculate the start date of the export
-        let export_period_start = last_monday(offset) - Duration::weeks(args.periods_ago as i64);
+                info!("Fetching customer {customer} from Exact");
+                SalesEntryTarget::New(SalesEntryHeader {
+                    customer: get_account_by_code(exact_client, customer).await?,
+                    entry_date: args.entry_date.unwrap_or(period.end),
+                    description: args
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Pretix {period}")),
+                })
+            }
+        };
 
-        // Get the exports
-        info!(
-            "Running Pretix exports with start date {}",
-            export_period_start
+        book_pretix(
+            &args.booking,
+            &period,
+            Some(&target),
+            program_args,
+            config,
+            external_clients,
+        )
+        .await
+    }
+}
+
+/// The Exact sales entry to which the Pretix lines are added.
+pub struct SalesEntryRef {
+    pub number: i32,
+    pub id: Guid,
+}
+
+/// The header of a sales entry to create.
+/// The journal is the configured sales journal.
+pub struct SalesEntryHeader {
+    pub customer: Guid,
+    pub entry_date: Date,
+    pub description: String,
+}
+
+/// Where the Pretix lines are booked.
+pub enum SalesEntryTarget {
+    /// Add the lines to an existing sales entry.
+    Existing(SalesEntryRef),
+    /// Create a new sales entry containing all lines, in one deep insert.
+    New(SalesEntryHeader),
+}
+
+/// Determine the period to book.
+/// The current date is determined in the configured timezone.
+///
+/// # Errors
+///
+/// - If the timezone does not exist
+/// - If the period is invalid, see [period_from_args]
+pub fn booking_period(args: &PretixBookingArgs, config: &Config) -> Result<Period> {
+    // Timezone hell
+    let tz = timezone(
+        args.timezone
+            .as_deref()
+            .or(config.timezone.as_deref())
+            .unwrap_or(DEFAULT_TIMEZONE),
+    )?;
+
+    // Determine the period to book
+    let today = OffsetDateTime::now_utc().to_timezone(tz).date();
+    let period = period_from_args(args, today)?;
+    info!(
+        "Booking period {period} ({} until {})",
+        period.start, period.end
+    );
+
+    Ok(period)
+}
+
+/// Run the Pretix exports for the period and book the resulting lines into the sales entry.
+/// The bookings are recorded in the ledger.
+///
+/// `target` may only be `None` in a dry run.
+///
+/// # Errors
+///
+/// See [prepare_pretix] and [PretixBooking::book]
+pub async fn book_pretix(
+    args: &PretixBookingArgs,
+    period: &Period,
+    target: Option<&SalesEntryTarget>,
+    program_args: &ProgramArgs,
+    config: &Config,
+    external_clients: &ExternalClients,
+) -> Result<()> {
+    prepare_pretix(args, period, program_args, config, external_clients)
+        .await?
+        .book(
+            period,
+            target,
+            program_args,
+            config,
+            &external_clients.exact,
+        )
+        .await
+}
+
+/// The Pretix lines of a period, checked and ready to be booked.
+pub struct PretixBooking {
+    ledger: Ledger,
+    date_axis: DateAxis,
+    policy: UnconfiguredEventPolicy,
+    bookings: Vec<EventBooking>,
+    failures: Vec<EventFailure>,
+}
+
+/// Run the Pretix exports for the period and determine the lines to book.
+/// Nothing is created in Exact, so the checks can run before anything else is booked.
+///
+/// # Errors
+///
+/// - If the period overlaps with an earlier booking and `--force` is not set
+/// - If an event is not configured correctly
+/// - If a request to Pretix or Exact fails
+pub async fn prepare_pretix(
+    args: &PretixBookingArgs,
+    period: &Period,
+    program_args: &ProgramArgs,
+    config: &Config,
+    external_clients: &ExternalClients,
+) -> Result<PretixBooking> {
+    let exact_client = &external_clients.exact;
+    let pretix_client = &external_clients.pretix;
+
+    // Read the bookings made in earlier runs
+    let ledger = Ledger::read(Ledger::path(program_args, config)).await?;
+
+    // The same date axis is used for the totals and the PDF report,
+    // so the booked totals match the attached report
+    let date_axis = args
+        .date_axis
+        .or(config.pretix.date_axis)
+        .unwrap_or(DateAxis::LastPaymentDate);
+
+    // The ledger is checked for all live events before running the exports, which are expensive.
+    // Events without orders in the period are not booked, but an earlier booking of the
+    // period still means the period was booked before.
+    let live_events = live_events(pretix_client).await?;
+    let event_ids = live_events
+        .iter()
+        .flat_map(|organizer| &organizer.events)
+        .map(|event| &event.slug)
+        .collect::<Vec<_>>();
+
+    // Refuse to book revenue that has already been booked
+    let overlapping = event_ids
+        .iter()
+        .flat_map(|event| {
+            ledger.overlapping(&event.to_string(), period.start, period.end, date_axis)
+        })
+        .collect::<Vec<_>>();
+
+    for record in &overlapping {
+        warn!(
+            "Event {} was already booked for {} until {} by {} into entry {} on {}",
+            record.event,
+            record.period_start,
+            record.period_end,
+            record.date_axis,
+            record.entry_number,
+            record.booked_at.date()
         );
-        let summaries = pretix_totals(pretix_client, export_period_start, offset).await?;
-        info!("Pretix exports complete");
+    }
+
+    if !overlapping.is_empty() {
+        if !args.force {
+            return Err(Error::msg(
+                "The period overlaps with earlier bookings. Use '--force' to book it anyway.",
+            ));
+        }
+
+        warn!("Flag '--force' set. Booking overlapping periods anyway");
+    }
+
+    // Periods on different date axes contain different orders, so they can not be checked for overlap
+    let other_axis = event_ids
+        .iter()
+        .flat_map(|event| ledger.other_axis(&event.to_string(), date_axis))
+        .collect::<Vec<_>>();
+
+    for record in &other_axis {
+        warn!(
+            "Event {} was booked by {} for {} until {}, this run books by {date_axis}",
+            record.event, record.date_axis, record.period_start, record.period_end
+        );
+    }
+
+    if !other_axis.is_empty() {
+        if !args.force {
+            return Err(Error::msg(format!(
+                "Events were booked by another date axis than {date_axis}. Mixing date axes can book orders twice or not at all. Use '--force' to book it anyway."
+            )));
+        }
+
+        warn!("Flag '--force' set. Booking by another date axis anyway");
+    }
+
+    // Resolve all configured GL accounts at once, later lookups are served from the cache.
+    // Like the ledger checks, this is done before the exports, as they are expensive
+    let gl_account_codes = config
+        .pretix
+        .event_specific
+        .values()
+        .flat_map(PretixEventConfig::gl_account_codes)
+        .chain([&config.exact.gl_accounts.bookkeeping])
+        .chain(&config.exact.gl_accounts.suspense)
+        .map(|code| code.0.as_str())
+        .collect::<Vec<_>>();
+    exact_client
+        .resolve_codes(CodeEntity::GLAccount, &gl_account_codes)
+        .await?;
+
+    let lookups = ExactLookups {
+        vat_codes: VatCodes::fetch(exact_client, &config.exact.vat_codes).await?,
+    };
+
+    // What to do with events that can not be booked
+    let policy = args
+        .unconfigured_events
+        .or(config.pretix.unconfigured_events)
+        .unwrap_or_default();
+    let suspense = match policy {
+        UnconfiguredEventPolicy::BookToSuspenseAccount => {
+            let code = config
+                .exact
+                .gl_accounts
+                .suspense
+                .as_ref()
+                .ok_or(Error::msg(
+                    "No suspense account configured for the 'book-to-suspense-account' policy",
+                ))?;
+            Some((code, get_gl_account_by_code(exact_client, code).await?))
+        }
+        _ => None,
+    };
 
-        let do_run = !program_args.dry_run;
+    // Get the exports
+    info!("Running Pretix exports");
+    let pretix_totals = pretix_totals(pretix_client, &live_events, period, date_axis).await?;
+    info!("Pretix exports complete");
 
-        for (event_key, summary) in summaries {
+    for (organizer, pdf) in &pretix_totals.reports {
+        info!("Organizer {organizer}: PDF report of {} bytes", pdf.len());
+    }
+
+    let mut bookings = Vec::new();
+    let mut failures = Vec::new();
+    for (event_key, summary) in pretix_totals.events {
+        info!(
+            "Event {}/{}: {:.2} with TRX {:.2}",
+            summary.organizer, event_key, summary.totals.value, summary.totals.fees
+        );
+        for item in &summary.items {
+            match &item.variation {
+                Some(variation) => info!(
+                    "Item: {} ({variation}) sold for {:.2}",
+                    item.item, item.value
+                ),
+                None => info!("Item: {} sold for {:.2}", item.item, item.value),
+            }
+        }
+        for total in vat_per_rate(&summary.items) {
             info!(
-                "Event {}: {:.2} with TRX {:.2}",
-                event_key, summary.totals.value, summary.totals.fees
+                "VAT {:.2}%: {:.2} excluding VAT, {:.2} VAT",
+                total.tax_rate, total.net, total.vat
             );
-            for (item_key, value) in &summary.items {
-                info!("Item: {item_key} sold for {value:.2}");
-            }
+        }
+        if summary.totals.fees_vat != 0.0 {
+            info!("VAT on fees: {:.2}", summary.totals.fees_vat);
+        }
 
-            // Get the event specific configuration
-            let event_config = config
-                .pretix
-                .event_specific
-                .get(&PretixEventId(event_key.to_string()))
-                .ok_or(Error::msg(format!(
-                    "No Event-specific configuration found for event {}",
-                    event_key
-                )))?;
-
-            // Get the Exact cost center GUID for each configured cost center
-            let cost_centers = try_join_all(event_config.cost_centers_per_product.iter().map(
-                |(pattern, cost_center_code)| async move {
-                    let cost_center_guid =
-                        get_cost_center_by_code(&exact_client, cost_center_code).await?;
-                    Ok::<_, Error>((
-                        Regex::new(pattern.as_ref())?,
-                        (cost_center_guid, cost_center_code),
-                    ))
-                },
-            ))
-            .await?;
-
-            // GL Account used in all rows except transaction costs
-            let gl_account =
-                get_gl_account_by_code(&exact_client, &event_config.gl_account).await?;
-
-            // For some events, like the introduction, the items sold should be split out in Exact.
-            // For other events, like external parties, this is not the case.
-            if event_config.split_per_product {
-                // General line name and transaction cost line name
-                let line_name = format!("Pretix {}", summary.event_name);
-                let trx_line_name = format!("{line_name} | Transactiekosten");
-
-                for (item_key, line_value) in &summary.items {
-                    // Some sold items shouldn't be in Exact, like 'Algemene Introductie'.
-                    // Check if we should skip the item
-                    let is_ignored = event_config
-                        .ignore_products
-                        .iter()
-                        .map(|pattern| Regex::new(pattern.as_ref()))
-                        .collect::<Result<Vec<_>, _>>()?
-                        .into_iter()
-                        .any(|regex| regex.is_match(item_key));
-
-                    if is_ignored {
-                        info!(
-                            "Item {}/{} is configured as ignored, ignoring.",
-                            event_key, item_key
-                        );
-                        continue;
+        if summary.items.is_empty() && summary.totals.fees == 0.0 {
+            info!("Event {event_key} has no orders in period {period}, skipping");
+            continue;
+        }
+
+        let lines = match event_lines(
+            exact_client,
+            config,
+            &event_key,
+            &summary,
+            period,
+            &lookups,
+            program_args.dry_run,
+        )
+        .await
+        {
+            Ok(lines) => lines,
+            Err(e) if e.downcast_ref::<EventConfigError>().is_some() => {
+                warn!("Event {event_key} can not be booked: {e}");
+                failures.push(EventFailure {
+                    event: event_key.clone(),
+                    error: e.to_string(),
+                });
+
+                match suspense {
+                    Some((code, ref gl_account)) => {
+                        suspense_lines(
+                            exact_client,
+                            config,
+                            &summary,
+                            period,
+                            (code, gl_account),
+                            &lookups,
+                        )
+                        .await?
                     }
+                    None => continue,
+                }
+            }
+            Err(e) => return Err(e),
+        };
 
-                    // Get the cost center associated with the itemd
-                    let cost_center = &cost_centers
-                        .iter()
-                        .find(|(pattern, _)| pattern.is_match(&item_key))
-                        .ok_or(Error::msg(format!(
-                            "No matching cost center pattern found for {}/{}",
-                            event_key, item_key
-                        )))?
-                        .1;
-
-                    // Find the Pretix sale item corresponding with the item key
-                    let sale_item = summary
-                        .sale_items
-                        .iter()
-                        .find(|sale_item| sale_item.name.eq(item_key))
-                        .ok_or(Error::msg(format!(
-                            "No sale item could be found for sold item {}/{}",
-                            event_key, item_key
-                        )))?;
-
-                    // Find the Exact VAT code for this item
-                    let vat_code = config
-                        .exact
-                        .vat_codes
+        bookings.push(EventBooking {
+            event: event_key,
+            value: summary.totals.value,
+            vat: summary.totals.vat,
+            fees: summary.totals.fees,
+            lines,
+        });
+    }
+
+    if policy == UnconfiguredEventPolicy::Fail && !failures.is_empty() {
+        log_failures(&failures, policy);
+        return Err(Error::msg(format!(
+            "{} events can not be booked, nothing was booked. \
+            Use '--unconfigured-events' to book the other events anyway.",
+            failures.len()
+        )));
+    }
+
+    // Inform the user of what we will do
+    for booking in &bookings {
+        for line in &booking.lines {
+            info!("Creating sale line in Exact: {line}");
+        }
+    }
+
+    // The VAT as it will be booked, per VAT code
+    let mut vat_per_code = BTreeMap::<&str, (i64, i64)>::new();
+    for line in bookings.iter().flat_map(|booking| &booking.lines) {
+        if let (Some(vat_code), Some(vat)) = (&line.vat_code, line.vat) {
+            let (net_total, vat_total) = vat_per_code.entry(vat_code).or_default();
+            *net_total += to_cents(line.amount);
+            *vat_total += to_cents(vat);
+        }
+    }
+    for (vat_code, (net, vat)) in vat_per_code {
+        info!(
+            "VAT code {vat_code}: {:.2} excluding VAT, {:.2} VAT",
+            from_cents(net),
+            from_cents(vat)
+        );
+    }
+
+    Ok(PretixBooking {
+        ledger,
+        date_axis,
+        policy,
+        bookings,
+        failures,
+    })
+}
+
+impl PretixBooking {
+    /// Book the lines into the sales entry and record the bookings in the ledger.
+    ///
+    /// `target` may only be `None` in a dry run.
+    ///
+    /// # Errors
+    ///
+    /// - If a request to Exact fails
+    /// - If the ledger could not be written
+    pub async fn book(
+        self,
+        period: &Period,
+        target: Option<&SalesEntryTarget>,
+        program_args: &ProgramArgs,
+        config: &Config,
+        exact_client: &ExactClient,
+    ) -> Result<()> {
+        let PretixBooking {
+            mut ledger,
+            date_axis,
+            policy,
+            bookings,
+            failures,
+        } = self;
+
+        if program_args.dry_run {
+            info!("Flag '--dry-run' set. Not inserting anything into Exact");
+            log_failures(&failures, policy);
+            return Ok(());
+        }
+
+        let target = target.ok_or(Error::msg("No sales entry to add the Pretix lines to"))?;
+        match target {
+            SalesEntryTarget::Existing(sales_entry) => {
+                // Each event is added in its own changeset and recorded right after,
+                // so a failure can't leave lines in Exact that the ledger doesn't know about
+                let sales_entry = SalesEntryRef {
+                    number: sales_entry.number,
+                    id: sales_entry.id.clone(),
+                };
+                for booking in bookings {
+                    let lines = booking
+                        .lines
                         .iter()
-                        .find(|code| code.percentage == sale_item.tax_rate)
-                        .ok_or(Error::msg(format!(
-                            "Could not find tax rate for item {}/{} with VAT percentage {}",
-                            event_key, item_key, sale_item.tax_rate
-                        )))?;
-
-                    // Format the line name
-                    let item_line_name = format!("{line_name} | {item_key}");
-
-                    // Inform the user of what we will do
-                    info!(
-                        "Creating sale line in Exact: {} {item_line_name} {} {}% €{:.2}",
-                        event_config.gl_account, cost_center.1, vat_code.percentage, line_value
-                    );
+                        .map(|line| line.new_line(Some(sales_entry.id.clone())))
+                        .collect::<Vec<_>>();
+                    create_sales_entry_lines(exact_client, &lines).await?;
+                    record_booking(&mut ledger, booking, period, date_axis, &sales_entry).await?;
+                }
+            }
+            SalesEntryTarget::New(header) => {
+                if bookings.is_empty() {
+                    info!("No Pretix lines to book. Not creating a sales entry");
+                    log_failures(&failures, policy);
+                    return Ok(());
+                }
+
+                // The entry is created with all lines in one request, so it is either complete or absent
+                let created = create_sales_entry(
+                    exact_client,
+                    &NewSalesEntry {
+                        journal: config.exact.journals.sales.clone(),
+                        customer: header.customer.clone(),
+                        entry_date: header.entry_date,
+                        description: header.description.clone(),
+                        lines: bookings
+                            .iter()
+                            .flat_map(|booking| &booking.lines)
+                            .map(|line| line.new_line(None))
+                            .collect(),
+                    },
+                )
+                .await?;
+                info!("Created sales entry {}", created.entry_number);
+
+                let sales_entry = SalesEntryRef {
+                    number: created.entry_number,
+                    id: created.entry_id,
+                };
+                for booking in bookings {
+                    record_booking(&mut ledger, booking, period, date_axis, &sales_entry).await?;
                 }
+            }
+        }
+
+        log_failures(&failures, policy);
+
+        Ok(())
+    }
+}
+
+/// Record an event booked into `sales_entry` in the ledger.
+async fn record_booking(
+    ledger: &mut Ledger,
+    booking: EventBooking,
+    period: &Period,
+    date_axis: DateAxis,
+    sales_entry: &SalesEntryRef,
+) -> Result<()> {
+    ledger
+        .append(LedgerRecord {
+            booked_at: OffsetDateTime::now_utc(),
+            event: booking.event.to_string(),
+            period_start: period.start,
+            period_end: period.end,
+            date_axis,
+            entry_number: sales_entry.number,
+            entry_id: sales_entry.id.clone(),
+            value: booking.value,
+            vat: booking.vat,
+            fees: booking.fees,
+            lines: booking.lines.iter().map(SaleLine::ledger_line).collect(),
+        })
+        .await?;
+
+    info!(
+        "Booked event {} into entry {}",
+        booking.event, sales_entry.number
+    );
+    Ok(())
+}
 
+/// The configuration of an event is missing or incomplete.
+/// Only these errors are handled by the [UnconfiguredEventPolicy],
+/// all other errors abort the run.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct EventConfigError(String);
+
+impl EventConfigError {
+    fn report(error: impl std::fmt::Display) -> Error {
+        Self(error.to_string()).into()
+    }
+}
+
+/// An event that could not be booked.
+struct EventFailure {
+    event: EventId,
+    error: String,
+}
+
+/// Summarize the events that could not be booked.
+fn log_failures(failures: &[EventFailure], policy: UnconfiguredEventPolicy) {
+    if failures.is_empty() {
+        return;
+    }
+
+    let outcome = match policy {
+        UnconfiguredEventPolicy::Fail => "not booked",
+        UnconfiguredEventPolicy::SkipWithWarning => "skipped",
+        UnconfiguredEventPolicy::BookToSuspenseAccount => "booked to the suspense account",
+    };
+
+    warn!("{} events could not be booked:", failures.len());
+    for failure in failures {
+        warn!("- {} ({outcome}): {}", failure.event, failure.error);
+    }
+}
+
+/// The sale lines for an event that is booked on the suspense account.
+/// The revenue is booked excluding VAT, one line per tax rate, the transaction costs as usual.
+///
+/// # Errors
+///
+/// - If there is no VAT code for a tax rate
+/// - If a GL account could not be resolved in Exact
+async fn suspense_lines(
+    exact_client: &ExactClient,
+    config: &Config,
+    summary: &EventSummary,
+    period: &Period,
+    (suspense_code, suspense_gl_account): (&ExactGLAccountCode, &Guid),
+    lookups: &ExactLookups<'_>,
+) -> Result<Vec<SaleLine>> {
+    let line_name = format!("Pretix {} | {period}", summary.event_name);
+
+    let mut lines = Vec::new();
+    for total in vat_per_rate(&summary.items) {
+        lines.push(SaleLine {
+            description: format!("{line_name} | Niet geconfigureerd"),
+            gl_account_code: suspense_code.clone(),
+            gl_account: suspense_gl_account.clone(),
+            cost_center: None,
+            cost_unit: None,
+            vat_code: Some(lookups.vat_codes.for_tax_rate(total.tax_rate)?.value),
+            amount: total.net,
+            vat: Some(total.vat),
+        });
+    }
+
+    let fee_lines = allocate_fees(
+        &FeeAllocation::default(),
+        summary.totals.fees,
+        summary.totals.fees_vat,
+        &summary.fees_per_provider,
+        &lines,
+        &config.exact.gl_accounts.bookkeeping,
+        &line_name,
+    );
+    lines.extend(fee_sale_lines(exact_client, fee_lines, summary, lookups).await?);
+
+    Ok(lines)
+}
+
+/// The sale lines for the fees of an event,
+/// booked excluding VAT on the VAT code for the tax rate of the fees.
+///
+/// # Errors
+///
+/// - If there is no VAT code for the tax rate of the fees
+/// - If a GL account could not be resolved in Exact
+async fn fee_sale_lines(
+    exact_client: &ExactClient,
+    fee_lines: Vec<FeeLine>,
+    summary: &EventSummary,
+    lookups: &ExactLookups<'_>,
+) -> Result<Vec<SaleLine>> {
+    if fee_lines.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let vat_code = lookups
+        .vat_codes
+        .for_tax_rate(summary.fees_tax_rate)
+        .map_err(EventConfigError::report)?
+        .value;
+
+    let mut lines = Vec::new();
+    for fee_line in fee_lines {
+        if let Some(cost_center) = &fee_line.cost_center {
+            resolve_configured(exact_client, CodeEntity::CostCenter, &cost_center.0).await?;
+        }
+
+        lines.push(SaleLine {
+            description: fee_line.description,
+            gl_account: resolve_configured(
+                exact_client,
+                CodeEntity::GLAccount,
+                &fee_line.gl_account.0,
+            )
+            .await?,
+            gl_account_code: fee_line.gl_account,
+            cost_center: fee_line.cost_center,
+            cost_unit: None,
+            vat_code: Some(vat_code.clone()),
+            amount: fee_line.amount,
+            vat: Some(fee_line.vat),
+        });
+    }
+
+    Ok(lines)
+}
+
+/// Resolve a code from the configuration in Exact.
+/// A code that does not exist in Exact is an [EventConfigError],
+/// so it is handled by the [UnconfiguredEventPolicy].
+async fn resolve_configured(
+    exact_client: &ExactClient,
+    entity: CodeEntity,
+    code: &str,
+) -> Result<Guid> {
+    match exact_client.resolve_code(entity, code).await {
+        Err(e @ ExactError::NotFound { .. }) => Err(EventConfigError::report(e)),
+        result => Ok(result?),
+    }
+}
+
+/// Data from Exact used for the lines of every event.
+struct ExactLookups<'a> {
+    /// VAT codes for the tax rates of the items
+    vat_codes: VatCodes<'a>,
+}
+
+/// The lines to book for one event.
+struct EventBooking {
+    event: EventId,
+    value: f32,
+    vat: f32,
+    fees: f32,
+    lines: Vec<SaleLine>,
+}
+
+/// Determine the sale lines for an event.
+/// If `explain` is set, the rules applied to each item are logged.
+///
+/// # Errors
+///
+/// - If there is no configuration for the event, or it is incomplete, as [EventConfigError]
+/// - If a configured GL account or cost center does not exist in Exact, as [EventConfigError]
+/// - If a request to Exact fails
+async fn event_lines(
+    exact_client: &ExactClient,
+    config: &Config,
+    event_key: &EventId,
+    summary: &EventSummary,
+    period: &Period,
+    lookups: &ExactLookups<'_>,
+    explain: bool,
+) -> Result<Vec<SaleLine>> {
+    // Get the event specific configuration
+    let event_config = config
+        .pretix
+        .event_specific
+        .get(&PretixEventId(event_key.to_string()))
+        .ok_or_else(|| {
+            EventConfigError::report(format!(
+                "No Event-specific configuration found for event {event_key}"
+            ))
+        })?;
+
+    // GL Account used in all rows except transaction costs
+    let gl_account = resolve_configured(
+        exact_client,
+        CodeEntity::GLAccount,
+        &event_config.gl_account.0,
+    )
+    .await?;
+
+    // General line name
+    let line_name = format!("Pretix {} | {period}", summary.event_name);
+
+    let mut lines: Vec<SaleLine> = Vec::new();
+
+    // For some events, like the introduction, the items sold should be split out in Exact.
+    // For other events, like external parties, this is not the case.
+    if event_config.split_per_product {
+        let rules = Rules::compile(&event_config.rules).map_err(EventConfigError::report)?;
+        let ignored = event_config
+            .ignore_products
+            .iter()
+            .map(|pattern| Regex::new(pattern.as_ref()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(EventConfigError::report)?;
+
+        for item in &summary.items {
+            // Some sold items shouldn't be in Exact, like 'Algemene Introductie'.
+            // Check if we should skip the item
+            if ignored.iter().any(|regex| regex.is_match(&item.item)) {
                 info!(
-                    "Creating sale line in Exact: {} {trx_line_name} €{:.2}",
-                    config.exact.gl_accounts.bookkeeping, summary.totals.fees
+                    "Item {}/{} is configured as ignored, ignoring.",
+                    event_key, item.item
                 );
+                continue;
+            }
+
+            let booking = rules
+                .resolve(
+                    item,
+                    event_config,
+                    &lookups.vat_codes,
+                    &summary.event_name,
+                    period,
+                )
+                .map_err(EventConfigError::report)?;
+
+            let item_name = match &item.variation {
+                Some(variation) => format!("{}/{} ({variation})", event_key, item.item),
+                None => format!("{}/{}", event_key, item.item),
+            };
+            if explain {
+                info!("Item {item_name}: {booking}");
             } else {
-                // Format the line name
-                let line_name = format!("Pretix {}", summary.event_name);
-                // Transaction cost line name
-                let trx_line_name = format!("{line_name} | Transactiekosten");
+                debug!("Item {item_name}: {booking}");
+            }
 
-                // Get the configured VAT code for this event
-                let vat_code = event_config.vat_code.as_ref().ok_or(Error::msg(format!(
-                    "Missing VAT code for event {event_key}"
-                )))?;
+            if let Some(cost_center) = &booking.cost_center {
+                resolve_configured(exact_client, CodeEntity::CostCenter, &cost_center.value.0)
+                    .await?;
+            }
 
-                // Inform the user of what we will do
-                info!(
-                    "Creating sale line in Exact: {} {line_name} {vat_code}% €{:.2}",
-                    event_config.gl_account, summary.totals.value
-                );
-                info!(
-                    "Creating sale line in Exact: {} {trx_line_name} €{:.2}",
-                    config.exact.gl_accounts.bookkeeping, summary.totals.fees
-                );
+            let gl_account_code = booking.gl_account.value;
+            let line = SaleLine {
+                description: booking.description.value,
+                gl_account: resolve_configured(
+                    exact_client,
+                    CodeEntity::GLAccount,
+                    &gl_account_code.0,
+                )
+                .await?,
+                gl_account_code,
+                cost_center: booking.cost_center.map(|c| c.value),
+                cost_unit: booking.cost_unit.map(|c| c.value),
+                vat_code: Some(booking.vat_code.value),
+                amount: item.net(),
+                vat: Some(item.vat),
+            };
+
+            // Items booked the same way, e.g. variations, are combined into one line
+            match lines.iter_mut().find(|other| other.books_like(&line)) {
+                Some(other) => {
+                    other.amount = from_cents(to_cents(other.amount) + to_cents(line.amount));
+                    other.vat = Some(from_cents(
+                        to_cents(other.vat.unwrap_or_default())
+                            + to_cents(line.vat.unwrap_or_default()),
+                    ));
+                }
+                None => lines.push(line),
             }
         }
+    } else {
+        // One line per tax rate, as Exact books each line on a single VAT code
+        for total in vat_per_rate(&summary.items) {
+            let vat_code = lookups
+                .vat_codes
+                .for_tax_rate(total.tax_rate)
+                .map_err(EventConfigError::report)?;
+            if explain {
+                info!("Tax rate {:.2}%: {vat_code}", total.tax_rate);
+            }
 
-        // TODO insert into Exact
-
-        Ok(())
+            lines.push(SaleLine {
+                description: line_name.clone(),
+                gl_account_code: event_config.gl_account.clone(),
+                gl_account: gl_account.clone(),
+                cost_center: None,
+                cost_unit: None,
+                vat_code: Some(vat_code.value),
+                amount: total.net,
+                vat: Some(total.vat),
+            });
+        }
     }
+
+    // Transaction costs, allocated as configured for the event
+    let fee_lines = allocate_fees(
+        &event_config.fees,
+        summary.totals.fees,
+        summary.totals.fees_vat,
+        &summary.fees_per_provider,
+        &lines,
+        &config.exact.gl_accounts.bookkeeping,
+        &line_name,
+    );
+    lines.extend(fee_sale_lines(exact_client, fee_lines, summary, lookups).await?);
+
+    Ok(lines)
 }
@@ -0,0 +1,312 @@
+use crate::args::PretixBookingArgs;
+use crate::modes::weekelijkse_plezier::time_util::last_monday;
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use std::fmt::Display;
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, Weekday};
+use time_tz::{OffsetDateTimeExt, PrimitiveDateTimeExt, TimeZone, Tz};
+
+/// A period of whole days for which the Pretix revenue is booked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    /// The first day of the period
+    pub start: Date,
+    /// The last day of the period, inclusive
+    pub end: Date,
+    pub kind: PeriodKind,
+}
+
+/// How the period was specified.
+/// Used to describe the period in line descriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeriodKind {
+    /// An ISO week, monday through sunday
+    Week { year: i32, week: u8 },
+    /// A calendar month
+    Month { year: i32, month: Month },
+    /// A calendar quarter
+    Quarter { year: i32, quarter: u8 },
+    /// Any range of days
+    Range,
+}
+
+impl Period {
+    /// The ISO week `week` of `year`.
+    ///
+    /// # Errors
+    ///
+    /// If the week does not exist in the year
+    pub fn iso_week(year: i32, week: u8) -> Result<Self> {
+        let start = Date::from_iso_week_date(year, week, Weekday::Monday)?;
+        Ok(Self {
+            start,
+            end: start + Duration::days(6),
+            kind: PeriodKind::Week { year, week },
+        })
+    }
+
+    /// The calendar month `month` of `year`.
+    ///
+    /// # Errors
+    ///
+    /// If the year is out of range
+    pub fn month(year: i32, month: Month) -> Result<Self> {
+        let start = Date::from_calendar_date(year, month, 1)?;
+        let end =
+            Date::from_calendar_date(year, month, time::util::days_in_year_month(year, month))?;
+        Ok(Self {
+            start,
+            end,
+            kind: PeriodKind::Month { year, month },
+        })
+    }
+
+    /// The calendar quarter `quarter` (1 through 4) of `year`.
+    ///
+    /// # Errors
+    ///
+    /// If the quarter is not within 1 through 4
+    pub fn quarter(year: i32, quarter: u8) -> Result<Self> {
+        if !(1..=4).contains(&quarter) {
+            return Err(Error::msg(format!(
+                "Quarter must be within 1 through 4, got {quarter}"
+            )));
+        }
+
+        let first_month = Month::try_from(quarter * 3 - 2)?;
+        let last_month = Month::try_from(quarter * 3)?;
+
+        Ok(Self {
+            start: Self::month(year, first_month)?.start,
+            end: Self::month(year, last_month)?.end,
+            kind: PeriodKind::Quarter { year, quarter },
+        })
+    }
+
+    /// The days from `from` up to and including `until`.
+    ///
+    /// # Errors
+    ///
+    /// If `until` is before `from`
+    pub fn range(from: Date, until: Date) -> Result<Self> {
+        if until < from {
+            return Err(Error::msg(format!(
+                "The end of the period ({until}) is before the start of the period ({from})"
+            )));
+        }
+
+        Ok(Self {
+            start: from,
+            end: until,
+            kind: PeriodKind::Range,
+        })
+    }
+
+    /// The monday through sunday week `weeks_ago` weeks before the week of `today`.
+    /// A value of 1 indicates the most recent finished week.
+    pub fn weeks_ago(today: Date, weeks_ago: u32) -> Result<Self> {
+        let monday = last_monday(today) - Duration::weeks(weeks_ago as i64);
+        let (year, week, _) = monday.to_iso_week_date();
+        Self::iso_week(year, week)
+    }
+
+    /// Check that the period can be booked.
+    ///
+    /// # Errors
+    ///
+    /// If the period has not finished yet at `today`.
+    pub fn validate(&self, today: Date) -> Result<()> {
+        if self.end >= today {
+            return Err(Error::msg(format!(
+                "The period {self} ({} until {}) has not finished yet",
+                self.start, self.end
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// The start and end of the period as points in time in the timezone `tz`.
+    /// The start is at midnight of the first day,
+    /// the end is at the last second of the last day.
+    ///
+    /// Each boundary uses the UTC offset valid at that instant,
+    /// so a period containing a DST switch has a different offset at its start and end.
+    ///
+    /// # Errors
+    ///
+    /// If a boundary does not exist in the timezone
+    pub fn boundaries(&self, tz: &Tz) -> Result<(OffsetDateTime, OffsetDateTime)> {
+        Ok((
+            Self::assume_timezone(self.start.with_time(Time::MIDNIGHT), tz)?,
+            Self::assume_timezone(self.end.with_time(Time::from_hms(23, 59, 59)?), tz)?,
+        ))
+    }
+
+    /// Whether the instant `datetime` falls on a day within the period.
+    /// The day is determined in the timezone `tz`, the same way Pretix
+    /// determines the day of an order for its reports.
+    pub fn contains(&self, datetime: OffsetDateTime, tz: &Tz) -> bool {
+        let date = datetime.to_timezone(tz).date();
+        date >= self.start && date <= self.end
+    }
+
+    /// Interpret a local time in the timezone `tz`.
+    /// If the time is ambiguous due to a DST switch, the earliest instant is used.
+    fn assume_timezone(datetime: PrimitiveDateTime, tz: &Tz) -> Result<OffsetDateTime> {
+        datetime
+            .assume_timezone(tz)
+            .take_first()
+            .ok_or(Error::msg(format!(
+                "{datetime} does not exist in timezone {}",
+                tz.name()
+            )))
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            PeriodKind::Week { week, .. } => write!(f, "week {week}"),
+            PeriodKind::Month { year, month } => write!(f, "{month} {year}"),
+            PeriodKind::Quarter { year, quarter } => write!(f, "Q{quarter} {year}"),
+            PeriodKind::Range => write!(f, "{} - {}", self.start, self.end),
+        }
+    }
+}
+
+/// Determine the period to book from the command line arguments.
+/// If no period is specified, the most recent finished week is used.
+///
+/// # Errors
+///
+/// - If the specified period is invalid
+/// - If the specified period has not finished yet
+pub fn period_from_args(args: &PretixBookingArgs, today: Date) -> Result<Period> {
+    let period = if let Some(week) = &args.week {
+        Period::iso_week(week.year, week.number)?
+    } else if let Some(month) = &args.month {
+        Period::month(month.year, month.number)?
+    } else if let Some(quarter) = &args.quarter {
+        Period::quarter(quarter.year, quarter.number)?
+    } else if let (Some(from), Some(until)) = (args.from, args.until) {
+        Period::range(from, until)?
+    } else {
+        let periods_ago = args.periods_ago.unwrap_or(1);
+        if periods_ago == 0 {
+            return Err(Error::msg("Argument '--periods-ago' may not be 0. 0 would mean you're looking from the most recent monday up until the next sunday, which isn't possible, as that sunday either hasn't happened yet, or it is still sunday."));
+        }
+
+        Period::weeks_ago(today, periods_ago)?
+    };
+
+    period.validate(today)?;
+    Ok(period)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Period;
+    use crate::modes::weekelijkse_plezier::time_util::timezone;
+    use time::macros::{date, datetime, offset};
+    use time::Month;
+
+    #[test]
+    fn iso_week() {
+        let period = Period::iso_week(2024, 42).unwrap();
+        assert_eq!(period.start, date!(2024 - 10 - 14));
+        assert_eq!(period.end, date!(2024 - 10 - 20));
+        assert_eq!(period.to_string(), "week 42");
+    }
+
+    #[test]
+    fn iso_week_across_years() {
+        let period = Period::iso_week(2025, 1).unwrap();
+        assert_eq!(period.start, date!(2024 - 12 - 30));
+        assert_eq!(period.end, date!(2025 - 01 - 05));
+    }
+
+    #[test]
+    fn month() {
+        let period = Period::month(2024, Month::February).unwrap();
+        assert_eq!(period.start, date!(2024 - 02 - 01));
+        assert_eq!(period.end, date!(2024 - 02 - 29));
+        assert_eq!(period.to_string(), "February 2024");
+    }
+
+    #[test]
+    fn quarter() {
+        let period = Period::quarter(2024, 4).unwrap();
+        assert_eq!(period.start, date!(2024 - 10 - 01));
+        assert_eq!(period.end, date!(2024 - 12 - 31));
+        assert!(Period::quarter(2024, 5).is_err());
+    }
+
+    #[test]
+    fn weeks_ago() {
+        // A wednesday
+        let period = Period::weeks_ago(date!(2024 - 10 - 23), 1).unwrap();
+        assert_eq!(period, Period::iso_week(2024, 42).unwrap());
+    }
+
+    #[test]
+    fn unfinished_period() {
+        let period = Period::iso_week(2024, 43).unwrap();
+        assert!(period.validate(date!(2024 - 10 - 27)).is_err());
+        assert!(period.validate(date!(2024 - 10 - 28)).is_ok());
+    }
+
+    #[test]
+    fn range_reversed() {
+        assert!(Period::range(date!(2024 - 10 - 02), date!(2024 - 10 - 01)).is_err());
+    }
+
+    #[test]
+    fn boundaries_without_dst_switch() {
+        let tz = timezone("Europe/Amsterdam").unwrap();
+        let (start, end) = Period::iso_week(2024, 42).unwrap().boundaries(tz).unwrap();
+        assert_eq!(start.offset(), offset!(+2));
+        assert_eq!(end.offset(), offset!(+2));
+    }
+
+    #[test]
+    fn boundaries_dst_end() {
+        // DST ends on sunday 2024-10-27
+        let tz = timezone("Europe/Amsterdam").unwrap();
+        let (start, end) = Period::iso_week(2024, 43).unwrap().boundaries(tz).unwrap();
+        assert_eq!(start.date(), date!(2024 - 10 - 21));
+        assert_eq!(start.offset(), offset!(+2));
+        assert_eq!(end.date(), date!(2024 - 10 - 27));
+        assert_eq!(end.offset(), offset!(+1));
+    }
+
+    #[test]
+    fn boundaries_dst_start() {
+        // DST starts on sunday 2024-03-31
+        let tz = timezone("Europe/Amsterdam").unwrap();
+        let (start, end) = Period::iso_week(2024, 13).unwrap().boundaries(tz).unwrap();
+        assert_eq!(start.date(), date!(2024 - 03 - 25));
+        assert_eq!(start.offset(), offset!(+1));
+        assert_eq!(end.date(), date!(2024 - 03 - 31));
+        assert_eq!(end.offset(), offset!(+2));
+    }
+
+    #[test]
+    fn boundaries_negative_offset() {
+        let tz = timezone("America/New_York").unwrap();
+        let (start, _) = Period::iso_week(2024, 42).unwrap().boundaries(tz).unwrap();
+        assert_eq!(start.offset(), offset!(-4));
+    }
+
+    #[test]
+    fn contains_local_day() {
+        let tz = timezone("Europe/Amsterdam").unwrap();
+        let period = Period::iso_week(2024, 43).unwrap();
+        // Sunday evening local time, after the DST switch
+        assert!(period.contains(datetime!(2024-10-27 22:59:59.5 UTC), tz));
+        // Monday morning local time
+        assert!(!period.contains(datetime!(2024-10-27 23:00:00 UTC), tz));
+        // Monday 00:30 local time, still sunday in UTC
+        assert!(period.contains(datetime!(2024-10-20 22:30:00 UTC), tz));
+    }
+}
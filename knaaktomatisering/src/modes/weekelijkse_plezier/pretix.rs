@@ -1,152 +1,474 @@
-use crate::modes::weekelijkse_plezier::time_util::pretix_export_period;
+use crate::modes::weekelijkse_plezier::period::Period;
+use crate::modes::weekelijkse_plezier::rounding::{from_cents, to_cents};
+use crate::modes::weekelijkse_plezier::time_util::timezone;
 use color_eyre::eyre::Error;
-use futures_util::future::try_join_all;
+use futures_util::future::{try_join, try_join_all};
 use pretix_request::data_exporter::{
-    DataExporter, ExportResponseSaleItem, OrderDataExportOrderItem,
+    DataExporter, DateAxis, ExportResponseSaleItem, OrderDataExportOrderItem, OrderExport,
 };
 use pretix_request::events::{Event, EventId};
-use pretix_request::organizer::Organizer;
+use pretix_request::organizer::{Organizer, OrganizerId};
 use pretix_request::PretixClient;
 use std::collections::HashMap;
-use std::num::ParseFloatError;
-use std::str::FromStr;
-use time::{OffsetDateTime, UtcOffset};
+use time_tz::Tz;
 use tracing::info;
 
 pub struct EventSummary {
+    pub organizer: OrganizerId,
     pub event_name: String,
     pub totals: OrderExportTotals,
-    pub pdf: Vec<u8>,
-    pub sale_items: Vec<ExportResponseSaleItem>,
-    pub items: HashMap<String, f32>,
+    /// The totals per item and variation
+    pub items: Vec<ItemTotal>,
+    /// The fees per payment provider.
+    /// Fees of orders without a successful payment are under `unknown`.
+    pub fees_per_provider: HashMap<String, f32>,
+    /// The tax rate of the fees, `0.0` if they have none
+    pub fees_tax_rate: f32,
 }
 
-/// Run a Pretix export for all available events for the export period.
+/// The total value of an item, or a variation of an item, sold in the period.
+#[derive(Debug, Clone)]
+pub struct ItemTotal {
+    pub item: String,
+    pub variation: Option<String>,
+    pub category: Option<String>,
+    pub tax_rate: f32,
+    /// The value including VAT
+    pub value: f32,
+    /// The VAT included in `value`
+    pub vat: f32,
+}
+
+impl ItemTotal {
+    /// The value excluding VAT.
+    pub fn net(&self) -> f32 {
+        from_cents(to_cents(self.value) - to_cents(self.vat))
+    }
+}
+
+/// The revenue and VAT of all items with the same tax rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VatTotal {
+    pub tax_rate: f32,
+    /// The value excluding VAT
+    pub net: f32,
+    pub vat: f32,
+}
+
+pub struct PretixTotals {
+    /// The summary of each live event.
+    pub events: HashMap<EventId, EventSummary>,
+    /// The PDF report of each organizer, covering all of its live events.
+    /// Pretix renders one report per export job, so it is not split per event.
+    pub reports: HashMap<OrganizerId, Vec<u8>>,
+}
+
+/// The live events of an organizer, the events that are exported.
+pub struct OrganizerEvents {
+    pub organizer: OrganizerId,
+    pub events: Vec<Event>,
+}
+
+/// List the live events of all organizers we have access to.
+/// Organizers without live events are left out.
+pub async fn live_events(pretix_client: &PretixClient) -> color_eyre::Result<Vec<OrganizerEvents>> {
+    let organizers = try_join_all(Organizer::list(pretix_client).await?.into_iter().map(
+        |organizer| async move {
+            // We do not need to check closed events
+            let events = Event::list(pretix_client, &organizer.slug)
+                .await?
+                .into_iter()
+                .filter(|event| event.live)
+                .collect::<Vec<_>>();
+
+            Ok::<_, Error>(OrganizerEvents {
+                organizer: organizer.slug,
+                events,
+            })
+        },
+    ))
+    .await?;
+
+    Ok(organizers
+        .into_iter()
+        .filter(|organizer| !organizer.events.is_empty())
+        .collect())
+}
+
+/// Run a Pretix export for the live events, see [live_events], for the period.
+///
+/// One multi-event export is run per organizer, which is then split per event.
+/// The orders of each event are assigned to days in the timezone of the event, as Pretix does for the report.
 pub async fn pretix_totals(
     pretix_client: &PretixClient,
-    export_period_start: OffsetDateTime,
-    offset: UtcOffset,
-) -> color_eyre::Result<HashMap<EventId, EventSummary>> {
-    let (period_start, period_end) = pretix_export_period(export_period_start, offset)?;
-    info!("Period end: {period_end}");
-
-    // List all organizers we have access to,
-    // within each organizer, list all events,
-    // for each event, run an export and compute the totals
-    let results = try_join_all(Organizer::list(pretix_client).await?.into_iter().map(
-        |organizer| async move {
-            try_join_all(
-                Event::list(pretix_client, &organizer.slug)
-                    .await?
-                    .into_iter()
-                    // We do not need to check closed events
-                    .filter(|event| event.live)
-                    .map(|event| {
-                        let organizer_id = &organizer.slug;
-                        async move {
-                            // Get all orders of this event
-                            let data_export = DataExporter::export_order_data(
-                                pretix_client,
-                                organizer_id,
-                                &event.slug,
-                            )
-                            .await?;
-
-                            // Keep only those within our export period
-                            let export_items = data_export
-                                .orders
-                                .into_iter()
-                                .filter(|order_item| {
-                                    order_item.datetime >= period_start
-                                        && order_item.datetime <= period_end
-                                })
-                                .collect::<Vec<_>>();
-
-
-                            // Compute totals
-                            let totals = order_export_calc_totals(&export_items);
-
-                            let pdf = DataExporter::export_order_data_pdf(
-                                pretix_client,
-                                organizer_id,
-                                &event.slug,
-                                period_start,
-                                period_end,
-                            )
-                            .await?;
-
-                            let totals_per_item = calc_order_totals_per_sale_item(&export_items)
-                                .into_iter()
-                                .map(|(item_id, item_value_sum)| {
-                                    let sale_item = data_export.items
-                                        .iter()
-                                        .find(|sale_item| sale_item.id == item_id)
-                                        .ok_or(Error::msg("Could not find sale item corresponding to sale item in order."))?;
-                                    Ok::<_, Error>((sale_item.name.clone(), item_value_sum))
-                                })
-                                .collect::<Result<HashMap<_, _>, _>>()?;
-
-                            let event_name = event
-                                .name
-                                .get("en")
-                                .cloned()
-                                .unwrap_or(event.slug.to_string().clone());
-
-                            Ok::<_, Error>((event.slug, EventSummary {
-                                event_name,
-                                sale_items: data_export.items,
-                                totals,
-                                pdf,
-                                items: totals_per_item,
-                            }))
-                        }
-                    }),
+    live_events: &[OrganizerEvents],
+    period: &Period,
+    date_axis: DateAxis,
+) -> color_eyre::Result<PretixTotals> {
+    info!(
+        "Period: {} until {} by {date_axis}",
+        period.start, period.end
+    );
+
+    // Run one export per organizer, covering all of its live events
+    let results = try_join_all(live_events.iter().map(
+        |OrganizerEvents { organizer, events }| async move {
+            let event_ids = events
+                .iter()
+                .map(|event| event.slug.clone())
+                .collect::<Vec<_>>();
+
+            // The report assigns orders to days in the timezone of their event
+            let timezones = try_join_all(events.iter().map(|event| async {
+                let name = Event::timezone(pretix_client, organizer, &event.slug).await?;
+                let tz = timezone(&name)?;
+                let (start, end) = period.boundaries(tz)?;
+                info!("Event {}: {start} until {end} ({name})", event.slug);
+                Ok::<_, Error>((event.slug.clone(), tz))
+            }))
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+            // Get all orders of all events and the report for the period
+            let (data_exports, pdf) = try_join(
+                DataExporter::export_organizer_order_data(pretix_client, organizer, &event_ids),
+                DataExporter::export_organizer_order_data_pdf(
+                    pretix_client,
+                    organizer,
+                    &event_ids,
+                    period.start,
+                    period.end,
+                    date_axis,
+                ),
             )
-            .await
+            .await?;
+
+            // Split the export per event
+            let summaries = data_exports
+                .into_iter()
+                .map(|data_export| {
+                    let event = events
+                        .iter()
+                        .find(|event| event.slug.eq(&data_export.slug))
+                        .ok_or(Error::msg(format!(
+                            "Export contains event {} which was not requested",
+                            data_export.slug
+                        )))?;
+
+                    let summary = event_summary(
+                        organizer,
+                        event,
+                        data_export.export,
+                        period,
+                        timezones[&event.slug],
+                        date_axis,
+                    )?;
+                    Ok::<_, Error>((data_export.slug, summary))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok::<_, Error>((organizer.clone(), summaries, pdf))
         },
     ))
-    .await?
-    .into_iter()
-    .flatten()
-    .collect::<HashMap<_, _>>();
+    .await?;
+
+    let mut totals = PretixTotals {
+        events: HashMap::new(),
+        reports: HashMap::new(),
+    };
+
+    for (organizer, summaries, pdf) in results {
+        totals.events.extend(summaries);
+        totals.reports.insert(organizer, pdf);
+    }
 
-    Ok(results)
+    Ok(totals)
 }
 
-pub fn calc_order_totals_per_sale_item(orders: &[OrderDataExportOrderItem]) -> HashMap<u32, f32> {
-    let positions = orders
-        .iter()
-        .map(|order| {
-            // Map the ordered items to map with K = item id and V = item price
-            order
-                .ordered_items
+/// Compute the summary of one event's orders within the export period.
+/// `event_tz` is the timezone of the event.
+fn event_summary(
+    organizer: &OrganizerId,
+    event: &Event,
+    data_export: OrderExport,
+    period: &Period,
+    event_tz: &Tz,
+    date_axis: DateAxis,
+) -> color_eyre::Result<EventSummary> {
+    let export_items = orders_in_period(data_export.orders, period, event_tz, date_axis);
+
+    // Compute totals
+    let mut totals = order_export_calc_totals(&export_items);
+
+    let fees_per_provider = calc_fees_per_provider(&export_items);
+    let fees_tax_rate = fees_tax_rate(&export_items)
+        .map_err(|e| Error::msg(format!("Event {organizer}/{}: {e}", event.slug)))?;
+
+    let mut items = calc_order_totals_per_sale_item(&export_items, &data_export.items)
+        .into_iter()
+        .map(|((item_id, variation_id), (value, vat))| {
+            let sale_item = data_export
+                .items
                 .iter()
-                .map(|v| (v.item, v.price))
-                .collect::<HashMap<_, _>>()
+                .find(|sale_item| sale_item.id == item_id)
+                .ok_or(Error::msg(
+                    "Could not find sale item corresponding to sale item in order.",
+                ))?;
+
+            let variation = variation_id
+                .map(|variation_id| {
+                    sale_item
+                        .variations
+                        .iter()
+                        .find(|variation| variation.id == variation_id)
+                        .map(|variation| variation.name.clone())
+                        .ok_or(Error::msg(format!(
+                            "Could not find variation {variation_id} of sale item {}",
+                            sale_item.name
+                        )))
+                })
+                .transpose()?;
+
+            let category = sale_item.category.and_then(|category_id| {
+                data_export
+                    .categories
+                    .iter()
+                    .find(|category| category.id == category_id)
+                    .map(|category| category.name.clone())
+            });
+
+            Ok::<_, Error>(ItemTotal {
+                item: sale_item.name.clone(),
+                variation,
+                category,
+                tax_rate: sale_item.tax_rate,
+                value,
+                vat,
+            })
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, _>>()?;
+    items.sort_by(|a, b| (&a.item, &a.variation).cmp(&(&b.item, &b.variation)));
 
-    // We now have a list of maps.
-    // Iterate over it and turn it into one map with K = item id and V = sum of prices for that item
-    let mut totals = HashMap::new();
-    for pos in positions {
-        for (item_id, item_value) in pos {
-            totals
-                .entry(item_id)
-                .and_modify(|values| *values += item_value)
-                .or_insert(item_value);
+    // What is booked in Exact has to tie back to Pretix to the cent
+    reconcile(&export_items, &mut totals, &items).map_err(|e| {
+        Error::msg(format!(
+            "Event {organizer}/{} does not add up: {e}",
+            event.slug
+        ))
+    })?;
+
+    let event_name = event
+        .name
+        .get("en")
+        .cloned()
+        .unwrap_or(event.slug.to_string());
+
+    Ok(EventSummary {
+        organizer: organizer.clone(),
+        event_name,
+        totals,
+        items,
+        fees_per_provider,
+        fees_tax_rate,
+    })
+}
+
+/// Keep only the orders within the period.
+/// This uses the same date axis and day boundaries as the PDF report:
+/// the day of an order is its date in the timezone of the event.
+fn orders_in_period(
+    orders: Vec<OrderDataExportOrderItem>,
+    period: &Period,
+    event_tz: &Tz,
+    date_axis: DateAxis,
+) -> Vec<OrderDataExportOrderItem> {
+    orders
+        .into_iter()
+        .filter(|order| {
+            order
+                .date(date_axis)
+                .is_some_and(|date| period.contains(date, event_tz))
+        })
+        .collect()
+}
+
+/// Sum the prices of the ordered items per item and variation,
+/// together with the VAT included in those prices.
+/// Prices are summed in whole cents, so the totals are exact however many positions there are.
+///
+/// The VAT of a position is taken from Pretix where available,
+/// and otherwise derived from the tax rate of the position or its sale item.
+pub fn calc_order_totals_per_sale_item(
+    orders: &[OrderDataExportOrderItem],
+    sale_items: &[ExportResponseSaleItem],
+) -> HashMap<(u32, Option<u32>), (f32, f32)> {
+    let mut totals = HashMap::<_, (i64, i64)>::new();
+    for position in orders.iter().flat_map(|order| &order.ordered_items) {
+        let tax_rate = position.tax_rate.or(sale_items
+            .iter()
+            .find(|sale_item| sale_item.id == position.item)
+            .map(|sale_item| sale_item.tax_rate));
+
+        let (value, vat) = totals
+            .entry((position.item, position.variation))
+            .or_default();
+        *value += to_cents(position.price);
+        *vat += included_vat(position.price, tax_rate, position.tax_value);
+    }
+
+    totals
+        .into_iter()
+        .map(|(key, (value, vat))| (key, (from_cents(value), from_cents(vat))))
+        .collect()
+}
+
+/// The VAT included in a gross amount, in cents.
+/// Pretix' own tax value is used if available, otherwise it is derived from the tax rate.
+/// Without either, the amount is assumed to include no VAT.
+fn included_vat(gross: f32, tax_rate: Option<f32>, tax_value: Option<f32>) -> i64 {
+    match (tax_value, tax_rate) {
+        (Some(tax_value), _) => to_cents(tax_value),
+        (None, Some(tax_rate)) => to_cents(gross * tax_rate / (100.0 + tax_rate)),
+        (None, None) => 0,
+    }
+}
+
+/// The revenue and VAT of the items per tax rate, ordered by tax rate.
+pub fn vat_per_rate<'a>(items: impl IntoIterator<Item = &'a ItemTotal>) -> Vec<VatTotal> {
+    let mut totals: Vec<VatTotal> = Vec::new();
+    for item in items {
+        match totals
+            .iter_mut()
+            .find(|total| (total.tax_rate - item.tax_rate).abs() < 0.005)
+        {
+            Some(total) => {
+                total.net = from_cents(to_cents(total.net) + to_cents(item.net()));
+                total.vat = from_cents(to_cents(total.vat) + to_cents(item.vat));
+            }
+            None => totals.push(VatTotal {
+                tax_rate: item.tax_rate,
+                net: item.net(),
+                vat: item.vat,
+            }),
         }
     }
 
+    totals.sort_by(|a, b| a.tax_rate.total_cmp(&b.tax_rate));
     totals
 }
 
+/// Check that the items plus the fees add up exactly to the totals of the orders,
+/// and set the event totals from the orders.
+/// Pretix stores every position, fee and order total in whole cents,
+/// so any difference is a real gap, e.g. positions missing from the export.
+/// The VAT of the event is the sum of the VAT of the items.
+///
+/// # Errors
+///
+/// If the items plus the fees do not add up to the totals of the orders
+fn reconcile(
+    orders: &[OrderDataExportOrderItem],
+    totals: &mut OrderExportTotals,
+    items: &[ItemTotal],
+) -> color_eyre::Result<()> {
+    let order_cents = orders
+        .iter()
+        .map(|order| to_cents(order.total))
+        .sum::<i64>();
+    let fee_cents = orders
+        .iter()
+        .flat_map(|order| &order.fees)
+        .map(|fee| to_cents(fee.value))
+        .sum::<i64>();
+    let item_cents = items.iter().map(|item| to_cents(item.value)).sum::<i64>();
+
+    if item_cents + fee_cents != order_cents {
+        return Err(Error::msg(format!(
+            "the items add up to {:.2} and the fees to {:.2}, but the orders total {:.2}",
+            from_cents(item_cents),
+            from_cents(fee_cents),
+            from_cents(order_cents),
+        )));
+    }
+
+    totals.value = from_cents(item_cents);
+    totals.vat = from_cents(items.iter().map(|item| to_cents(item.vat)).sum::<i64>());
+    totals.fees = from_cents(fee_cents);
+
+    Ok(())
+}
+
+/// Sum the fees of the orders per payment provider of the order.
+pub fn calc_fees_per_provider(orders: &[OrderDataExportOrderItem]) -> HashMap<String, f32> {
+    let mut totals = HashMap::<_, i64>::new();
+    for order in orders {
+        let provider = order
+            .payments
+            .iter()
+            .find(|payment| payment.is_successful())
+            .map(|payment| payment.provider.clone())
+            .unwrap_or("unknown".to_string());
+
+        *totals.entry(provider).or_default() += order
+            .fees
+            .iter()
+            .map(|fee| to_cents(fee.value))
+            .sum::<i64>();
+    }
+
+    totals
+        .into_iter()
+        .map(|(provider, fees)| (provider, from_cents(fees)))
+        .collect()
+}
+
+/// The tax rate of the fees of the orders, `0.0` if they have none.
+/// The fees are booked on one VAT code, so they must share their tax rate.
+///
+/// # Errors
+///
+/// If the fees have different tax rates
+fn fees_tax_rate(orders: &[OrderDataExportOrderItem]) -> color_eyre::Result<f32> {
+    let mut tax_rates: Vec<f32> = Vec::new();
+    for fee in orders.iter().flat_map(|order| &order.fees) {
+        if fee.value == 0.0 {
+            continue;
+        }
+
+        let tax_rate = fee.tax_rate.unwrap_or_default();
+        if !tax_rates
+            .iter()
+            .any(|other| (other - tax_rate).abs() < 0.005)
+        {
+            tax_rates.push(tax_rate);
+        }
+    }
+
+    match tax_rates.as_slice() {
+        [] => Ok(0.0),
+        [tax_rate] => Ok(*tax_rate),
+        _ => Err(Error::msg(format!(
+            "the fees have different tax rates ({})",
+            tax_rates
+                .iter()
+                .map(|tax_rate| format!("{tax_rate:.2}%"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
 #[derive(Debug)]
 pub struct OrderExportTotals {
-    /// The total amount without VAT or fees
+    /// The total amount including VAT, without fees
     pub value: f32,
-    /// The total fees without VAT or fees
+    /// The VAT included in `value`
+    pub vat: f32,
+    /// The total fees including VAT
     pub fees: f32,
+    /// The VAT included in `fees`
+    pub fees_vat: f32,
 }
 
 /// Calculate the totals for the provided set of order items.
@@ -154,15 +476,256 @@ pub fn order_export_calc_totals(items: &[OrderDataExportOrderItem]) -> OrderExpo
     let (value, fees) = items
         .iter()
         .map(|item| {
-            let fees = item.fees.iter().map(|fee_item| fee_item.value).sum::<f32>();
+            let fees = item
+                .fees
+                .iter()
+                .map(|fee_item| to_cents(fee_item.value))
+                .sum::<i64>();
 
-            (item.total - fees, fees)
+            (to_cents(item.total) - fees, fees)
         })
-        .collect::<Vec<(f32, f32)>>()
-        .into_iter()
-        .fold((0f32, 0f32), |(acc_value, acc_fee), (value, fee)| {
+        .fold((0i64, 0i64), |(acc_value, acc_fee), (value, fee)| {
             (acc_value + value, acc_fee + fee)
         });
 
-    OrderExportTotals { value, fees }
+    let fees_vat = items
+        .iter()
+        .flat_map(|item| &item.fees)
+        .map(|fee| included_vat(fee.value, fee.tax_rate, fee.tax_value))
+        .sum::<i64>();
+
+    OrderExportTotals {
+        value: from_cents(value),
+        // Known once the items are reconciled
+        vat: 0.0,
+        fees: from_cents(fees),
+        fees_vat: from_cents(fees_vat),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretix_request::data_exporter::{
+        OrderDataExportOrderItemFee, OrderDataExportOrderedItem, OrderDataExportPayment,
+    };
+    use time::macros::datetime;
+
+    fn order(prices: &[(u32, f32)], fees: &[f32], total: f32) -> OrderDataExportOrderItem {
+        OrderDataExportOrderItem {
+            fees: fees
+                .iter()
+                .map(|value| OrderDataExportOrderItemFee {
+                    value: *value,
+                    tax_rate: None,
+                    tax_value: None,
+                })
+                .collect(),
+            datetime: datetime!(2024-08-20 12:00 UTC),
+            total,
+            ordered_items: prices
+                .iter()
+                .map(|(item, price)| OrderDataExportOrderedItem {
+                    item: *item,
+                    variation: None,
+                    price: *price,
+                    tax_rate: None,
+                    tax_value: None,
+                })
+                .collect(),
+            payments: Vec::new(),
+        }
+    }
+
+    fn sale_item(id: u32, tax_rate: f32) -> ExportResponseSaleItem {
+        ExportResponseSaleItem {
+            id,
+            name: id.to_string(),
+            tax_rate,
+            category: None,
+            variations: Vec::new(),
+        }
+    }
+
+    fn items(orders: &[OrderDataExportOrderItem]) -> Vec<ItemTotal> {
+        let sale_items = [sale_item(1, 21.0), sale_item(2, 21.0)];
+        let mut totals = calc_order_totals_per_sale_item(orders, &sale_items)
+            .into_iter()
+            .collect::<Vec<_>>();
+        totals.sort_by_key(|((item, _), _)| *item);
+
+        totals
+            .into_iter()
+            .map(|((item, _), (value, vat))| ItemTotal {
+                item: item.to_string(),
+                variation: None,
+                category: None,
+                tax_rate: 21.0,
+                value,
+                vat,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reconciled_to_the_cent() {
+        let orders = (0..5000)
+            .map(|_| order(&[(1, 0.1), (2, 12.99)], &[0.35], 13.44))
+            .collect::<Vec<_>>();
+        let mut totals = order_export_calc_totals(&orders);
+        let items = items(&orders);
+
+        reconcile(&orders, &mut totals, &items).unwrap();
+
+        assert_eq!(50000, to_cents(items[0].value));
+        assert_eq!(6495000, to_cents(items[1].value));
+        assert_eq!(6545000, to_cents(totals.value));
+        assert_eq!(175000, to_cents(totals.fees));
+    }
+
+    #[test]
+    fn one_cent_missing_from_many_orders() {
+        let mut orders = (0..5000)
+            .map(|_| order(&[(1, 0.1), (2, 12.99)], &[0.35], 13.44))
+            .collect::<Vec<_>>();
+        orders.push(order(&[(2, 12.99)], &[], 13.0));
+        let mut totals = order_export_calc_totals(&orders);
+        let items = items(&orders);
+
+        assert!(reconcile(&orders, &mut totals, &items).is_err());
+    }
+
+    #[test]
+    fn fees_per_provider_in_cents() {
+        let orders = (0..5000)
+            .map(|_| order(&[(1, 0.1)], &[0.29], 0.39))
+            .collect::<Vec<_>>();
+
+        let fees = calc_fees_per_provider(&orders);
+
+        assert_eq!(145000, to_cents(fees["unknown"]));
+    }
+
+    #[test]
+    fn positions_missing_from_total() {
+        // The order total includes a position that is not in the export
+        let orders = [order(&[(1, 10.0)], &[0.35], 20.35)];
+        let mut totals = order_export_calc_totals(&orders);
+        let items = items(&orders);
+
+        assert!(reconcile(&orders, &mut totals, &items).is_err());
+    }
+
+    #[test]
+    fn one_tax_rate_for_the_fees() {
+        let with_fee = |value: f32, tax_rate: Option<f32>| {
+            let mut order = order(&[(1, 10.0)], &[], 10.0 + value);
+            order.fees.push(OrderDataExportOrderItemFee {
+                value,
+                tax_rate,
+                tax_value: None,
+            });
+            order
+        };
+
+        assert_eq!(
+            0.0,
+            fees_tax_rate(&[order(&[(1, 10.0)], &[], 10.0)]).unwrap()
+        );
+        assert_eq!(
+            21.0,
+            fees_tax_rate(&[with_fee(0.35, Some(21.0)), with_fee(0.0, Some(9.0))]).unwrap()
+        );
+        assert!(fees_tax_rate(&[with_fee(0.35, Some(21.0)), with_fee(0.35, None)]).is_err());
+    }
+
+    #[test]
+    fn vat_from_pretix_or_tax_rate() {
+        let mut ticket = order(&[(1, 12.1)], &[], 12.1);
+        ticket.ordered_items[0].tax_value = Some(2.1);
+        // No tax value from Pretix, 9% on the position
+        let mut drink = order(&[(2, 3.0)], &[], 3.0);
+        drink.ordered_items[0].tax_rate = Some(9.0);
+        // Neither, the rate of the sale item is used
+        let shirt = order(&[(3, 15.0)], &[], 15.0);
+
+        let sale_items = [sale_item(1, 21.0), sale_item(2, 21.0), sale_item(3, 21.0)];
+        let totals = calc_order_totals_per_sale_item(&[ticket, drink, shirt], &sale_items);
+
+        assert_eq!((12.1, 2.1), totals[&(1, None)]);
+        assert_eq!((3.0, 0.25), totals[&(2, None)]);
+        assert_eq!((15.0, 2.6), totals[&(3, None)]);
+    }
+
+    #[test]
+    fn vat_totals_per_rate() {
+        let item = |tax_rate: f32, value: f32, vat: f32| ItemTotal {
+            item: "Ticket".to_string(),
+            variation: None,
+            category: None,
+            tax_rate,
+            value,
+            vat,
+        };
+
+        let totals = vat_per_rate(&[
+            item(21.0, 12.1, 2.1),
+            item(9.0, 10.9, 0.9),
+            item(21.0, 6.05, 1.05),
+        ]);
+
+        assert_eq!(
+            vec![
+                VatTotal {
+                    tax_rate: 9.0,
+                    net: 10.0,
+                    vat: 0.9,
+                },
+                VatTotal {
+                    tax_rate: 21.0,
+                    net: 15.0,
+                    vat: 3.15,
+                },
+            ],
+            totals
+        );
+    }
+
+    #[test]
+    fn orders_on_report_days_of_the_event() {
+        let paid_at = |at| {
+            let mut order = order(&[(1, 10.0)], &[], 10.0);
+            order.payments = vec![OrderDataExportPayment {
+                state: "confirmed".to_string(),
+                provider: "mollie".to_string(),
+                payment_date: Some(at),
+            }];
+            order
+        };
+        let orders = vec![
+            // Sunday evening in Amsterdam, in the week before
+            paid_at(datetime!(2024-10-20 21:30 UTC)),
+            // Sunday in UTC, but already monday in Amsterdam, as in the report
+            paid_at(datetime!(2024-10-20 22:30 UTC)),
+            // Last second of sunday in Amsterdam
+            paid_at(datetime!(2024-10-27 22:59:59 UTC)),
+            // Monday of the next week in Amsterdam
+            paid_at(datetime!(2024-10-27 23:00 UTC)),
+        ];
+
+        let week = Period::iso_week(2024, 43).unwrap();
+        let amsterdam = timezone("Europe/Amsterdam").unwrap();
+        let in_period = orders_in_period(orders, &week, amsterdam, DateAxis::LastPaymentDate);
+
+        assert_eq!(
+            vec![
+                Some(datetime!(2024-10-20 22:30 UTC)),
+                Some(datetime!(2024-10-27 22:59:59 UTC)),
+            ],
+            in_period
+                .iter()
+                .map(|order| order.date(DateAxis::LastPaymentDate))
+                .collect::<Vec<_>>()
+        );
+    }
 }
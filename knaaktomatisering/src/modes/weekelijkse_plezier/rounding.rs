@@ -0,0 +1,92 @@
+/// Convert an amount to whole cents.
+pub fn to_cents(amount: f32) -> i64 {
+    (amount as f64 * 100.0).round() as i64
+}
+
+/// Convert whole cents to an amount.
+pub fn from_cents(cents: i64) -> f32 {
+    (cents as f64 / 100.0) as f32
+}
+
+/// Divide `total` cents over the `weights` with the largest remainder method.
+/// The result adds up to `total` exactly. Every share is rounded down, after which
+/// the remaining cents go to the shares with the largest fractional parts.
+///
+/// Returns `None` if the weights add up to zero.
+pub fn allocate(total: i64, weights: &[f64]) -> Option<Vec<i64>> {
+    let weight_sum = weights.iter().sum::<f64>();
+    if weight_sum == 0.0 {
+        return None;
+    }
+
+    let exact = weights
+        .iter()
+        .map(|weight| total as f64 * weight / weight_sum)
+        .collect::<Vec<_>>();
+
+    Some(distribute(total, &exact))
+}
+
+/// Round the `exact` shares down, and hand out the cents remaining to `total`
+/// in the order of the largest fractional parts.
+fn distribute(total: i64, exact: &[f64]) -> Vec<i64> {
+    let mut shares = exact
+        .iter()
+        .map(|share| share.floor() as i64)
+        .collect::<Vec<_>>();
+
+    let mut by_remainder = (0..shares.len()).collect::<Vec<_>>();
+    by_remainder.sort_by(|&a, &b| {
+        let remainder = |i: usize| exact[i] - exact[i].floor();
+        remainder(b).total_cmp(&remainder(a)).then(a.cmp(&b))
+    });
+
+    let remaining = total - shares.iter().sum::<i64>();
+    for i in by_remainder
+        .into_iter()
+        .cycle()
+        .take(remaining.max(0) as usize)
+    {
+        shares[i] += 1;
+    }
+
+    shares
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cents() {
+        assert_eq!(1010, to_cents(10.1));
+        assert_eq!(-29, to_cents(-0.29));
+        assert_eq!(10.1, from_cents(1010));
+    }
+
+    #[test]
+    fn allocate_largest_remainder() {
+        // 100 cents over three equal weights: 33.33 each, one cent remains
+        assert_eq!(Some(vec![34, 33, 33]), allocate(100, &[1.0, 1.0, 1.0]));
+        // 0.29 over 10.00 and 20.00: 9.67 and 19.33
+        assert_eq!(Some(vec![10, 19]), allocate(29, &[10.0, 20.0]));
+        // Negative totals, e.g. refunds
+        assert_eq!(
+            Some(vec![-33, -33, -34]),
+            allocate(-100, &[1.0, 1.0, 1.0]).map(|mut v| {
+                v.sort_by(|a, b| b.cmp(a));
+                v
+            })
+        );
+        assert_eq!(None, allocate(100, &[0.0, 0.0]));
+    }
+
+    #[test]
+    fn allocate_adds_up() {
+        let weights = [12.35, 0.5, 99.99, 7.0, 3.33];
+        for total in [0, 1, 7, 1234, -58] {
+            let shares = allocate(total, &weights).unwrap();
+            assert_eq!(total, shares.iter().sum::<i64>());
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use crate::config::{
+    ExactCostCenterCode, ExactCostUnitCode, ExactGLAccountCode, PretixEventConfig, ProductRule,
+    RegexPattern,
+};
+use crate::modes::weekelijkse_plezier::period::Period;
+use crate::modes::weekelijkse_plezier::pretix::ItemTotal;
+use crate::modes::weekelijkse_plezier::vat::VatCodes;
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use regex::Regex;
+use std::fmt::Display;
+
+/// The description used if no rule provides one.
+pub const DEFAULT_DESCRIPTION: &str = "Pretix {event} | {period} | {item}";
+
+/// The product rules of an event, with their patterns compiled.
+pub struct Rules<'a> {
+    rules: Vec<CompiledRule<'a>>,
+}
+
+struct CompiledRule<'a> {
+    rule: &'a ProductRule,
+    item: Option<Regex>,
+    category: Option<Regex>,
+    variation: Option<Regex>,
+}
+
+/// Where a setting of a line comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The rule with this index, starting at 0
+    Rule(usize),
+    /// The event configuration
+    Event,
+    /// The VAT code in Exact for the tax rate
+    TaxRate,
+    /// The VAT code configured for the tax rate
+    VatOverride,
+}
+
+/// A setting of a line and where it comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+/// How an item is booked, as determined by the rules.
+#[derive(Debug)]
+pub struct ItemBooking {
+    pub gl_account: Resolved<ExactGLAccountCode>,
+    pub cost_center: Option<Resolved<ExactCostCenterCode>>,
+    pub cost_unit: Option<Resolved<ExactCostUnitCode>>,
+    pub vat_code: Resolved<String>,
+    pub description: Resolved<String>,
+}
+
+impl<'a> Rules<'a> {
+    /// Compile the patterns of the rules.
+    ///
+    /// # Errors
+    ///
+    /// If a pattern is not a valid regex
+    pub fn compile(rules: &'a [ProductRule]) -> Result<Self> {
+        let compile = |pattern: &Option<RegexPattern>| {
+            pattern
+                .as_ref()
+                .map(|pattern| Regex::new(pattern.as_ref()))
+                .transpose()
+        };
+
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                Ok::<_, Error>(CompiledRule {
+                    rule,
+                    item: compile(&rule.matches.item)?,
+                    category: compile(&rule.matches.category)?,
+                    variation: compile(&rule.matches.variation)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// Determine how an item is booked.
+    ///
+    /// # Errors
+    ///
+    /// - If no rule matches the item
+    /// - If no rule provides a VAT code and no VAT code can be found for the tax rate of the item
+    pub fn resolve(
+        &self,
+        item: &ItemTotal,
+        event_config: &PretixEventConfig,
+        vat_codes: &VatCodes,
+        event_name: &str,
+        period: &Period,
+    ) -> Result<ItemBooking> {
+        let matching = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.matches(item))
+            .map(|(i, rule)| (i, rule.rule))
+            .collect::<Vec<_>>();
+
+        // A rule without conditions matches every item, so a fallback is explicit
+        if matching.is_empty() {
+            return Err(Error::msg(format!(
+                "No rule matches item {}. Add a rule for it, or add it to 'ignore_products'",
+                item.item
+            )));
+        }
+
+        // The first matching rule providing the setting
+        fn first<T: Clone>(
+            matching: &[(usize, &ProductRule)],
+            f: impl Fn(&ProductRule) -> Option<&T>,
+        ) -> Option<Resolved<T>> {
+            matching.iter().find_map(|(i, rule)| {
+                f(rule).map(|value| Resolved {
+                    value: value.clone(),
+                    source: Source::Rule(*i),
+                })
+            })
+        }
+
+        let gl_account = first(&matching, |rule| rule.gl_account.as_ref()).unwrap_or(Resolved {
+            value: event_config.gl_account.clone(),
+            source: Source::Event,
+        });
+
+        let vat_code = match first(&matching, |rule| rule.vat_code.as_ref()) {
+            Some(vat_code) => vat_code,
+            None => vat_codes
+                .for_tax_rate(item.tax_rate)
+                .map_err(|e| Error::msg(format!("No VAT code for item {}: {e}", item.item)))?,
+        };
+
+        let description = first(&matching, |rule| rule.description.as_ref()).unwrap_or(Resolved {
+            value: DEFAULT_DESCRIPTION.to_string(),
+            source: Source::Event,
+        });
+
+        Ok(ItemBooking {
+            gl_account,
+            cost_center: first(&matching, |rule| rule.cost_center.as_ref()),
+            cost_unit: first(&matching, |rule| rule.cost_unit.as_ref()),
+            vat_code,
+            description: Resolved {
+                value: render_description(&description.value, item, event_name, period),
+                source: description.source,
+            },
+        })
+    }
+}
+
+impl CompiledRule<'_> {
+    fn matches(&self, item: &ItemTotal) -> bool {
+        let pattern_matches = |pattern: &Option<Regex>, value: Option<&str>| match pattern {
+            None => true,
+            Some(pattern) => value.is_some_and(|value| pattern.is_match(value)),
+        };
+
+        pattern_matches(&self.item, Some(&item.item))
+            && pattern_matches(&self.category, item.category.as_deref())
+            && pattern_matches(&self.variation, item.variation.as_deref())
+            && self
+                .rule
+                .matches
+                .tax_rate
+                .is_none_or(|tax_rate| (tax_rate - item.tax_rate).abs() < 0.005)
+    }
+}
+
+/// Fill in the placeholders of a description template.
+fn render_description(
+    template: &str,
+    item: &ItemTotal,
+    event_name: &str,
+    period: &Period,
+) -> String {
+    template
+        .replace("{event}", event_name)
+        .replace("{period}", &period.to_string())
+        .replace("{item}", &item.item)
+        .replace("{variation}", item.variation.as_deref().unwrap_or_default())
+        .replace("{category}", item.category.as_deref().unwrap_or_default())
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rule(i) => write!(f, "rule {}", i + 1),
+            Self::Event => write!(f, "event"),
+            Self::TaxRate => write!(f, "tax rate"),
+            Self::VatOverride => write!(f, "configured VAT code"),
+        }
+    }
+}
+
+impl<T: Display> Display for Resolved<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.value, self.source)
+    }
+}
+
+impl Display for ItemBooking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GL account {}, VAT code {}",
+            self.gl_account, self.vat_code
+        )?;
+        if let Some(cost_center) = &self.cost_center {
+            write!(f, ", cost center {cost_center}")?;
+        }
+        if let Some(cost_unit) = &self.cost_unit {
+            write!(f, ", cost unit {cost_unit}")?;
+        }
+        write!(f, ", description from {}", self.description.source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{FeeAllocation, ProductMatch, VATCode};
+    use std::collections::HashMap;
+    use time::macros::date;
+
+    fn item(item: &str, variation: Option<&str>, category: &str, tax_rate: f32) -> ItemTotal {
+        ItemTotal {
+            item: item.to_string(),
+            variation: variation.map(str::to_string),
+            category: Some(category.to_string()),
+            tax_rate,
+            value: 10.0,
+            vat: 0.0,
+        }
+    }
+
+    fn event_config(rules: Vec<ProductRule>) -> PretixEventConfig {
+        PretixEventConfig {
+            gl_account: ExactGLAccountCode("8000".to_string()),
+            vat_code: None,
+            split_per_product: true,
+            rules,
+            cost_centers_per_product: HashMap::new(),
+            fees: FeeAllocation::default(),
+            ignore_products: Vec::new(),
+        }
+    }
+
+    fn vat_overrides() -> Vec<VATCode> {
+        vec![
+            VATCode {
+                percentage: 21.0,
+                code: "2".to_string(),
+            },
+            VATCode {
+                percentage: 9.0,
+                code: "1".to_string(),
+            },
+        ]
+    }
+
+    fn intro_rules() -> Vec<ProductRule> {
+        vec![
+            ProductRule {
+                matches: ProductMatch {
+                    category: Some(RegexPattern("^Merchandise$".to_string())),
+                    ..Default::default()
+                },
+                gl_account: Some(ExactGLAccountCode("8010".to_string())),
+                description: Some("Pretix {event} | {item} {variation}".to_string()),
+                ..Default::default()
+            },
+            ProductRule {
+                matches: ProductMatch {
+                    item: Some(RegexPattern("Shirt".to_string())),
+                    variation: Some(RegexPattern("^XL$".to_string())),
+                    ..Default::default()
+                },
+                cost_unit: Some(ExactCostUnitCode("SHIRT-XL".to_string())),
+                ..Default::default()
+            },
+            ProductRule {
+                cost_center: Some(ExactCostCenterCode("INTRO".to_string())),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn first_matching_rule_per_setting() {
+        let rules = intro_rules();
+        let config = event_config(rules);
+        let rules = Rules::compile(&config.rules).unwrap();
+        let period = Period::range(date!(2024 - 08 - 19), date!(2024 - 08 - 25)).unwrap();
+        let overrides = vat_overrides();
+        let vat_codes = VatCodes::new(&overrides, Vec::new());
+
+        let shirt = item("Shirt", Some("XL"), "Merchandise", 21.0);
+        let booking = rules
+            .resolve(&shirt, &config, &vat_codes, "Intro", &period)
+            .unwrap();
+
+        assert_eq!("8010", booking.gl_account.value.0);
+        assert_eq!(Source::Rule(0), booking.gl_account.source);
+        assert_eq!(Some(Source::Rule(1)), booking.cost_unit.map(|c| c.source));
+        assert_eq!(Some(Source::Rule(2)), booking.cost_center.map(|c| c.source));
+        assert_eq!("2", booking.vat_code.value);
+        assert_eq!(Source::VatOverride, booking.vat_code.source);
+        assert_eq!("Pretix Intro | Shirt XL", booking.description.value);
+
+        let ticket = item("Ticket", None, "Tickets", 9.0);
+        let booking = rules
+            .resolve(&ticket, &config, &vat_codes, "Intro", &period)
+            .unwrap();
+
+        assert_eq!("8000", booking.gl_account.value.0);
+        assert_eq!(Source::Event, booking.gl_account.source);
+        assert!(booking.cost_unit.is_none());
+        assert_eq!("1", booking.vat_code.value);
+        assert_eq!(
+            format!("Pretix Intro | {period} | Ticket"),
+            booking.description.value
+        );
+    }
+
+    #[test]
+    fn tax_rate_condition() {
+        let config = event_config(vec![
+            ProductRule {
+                matches: ProductMatch {
+                    tax_rate: Some(0.0),
+                    ..Default::default()
+                },
+                vat_code: Some("0".to_string()),
+                ..Default::default()
+            },
+            ProductRule::default(),
+        ]);
+        let rules = Rules::compile(&config.rules).unwrap();
+        let period = Period::range(date!(2024 - 08 - 19), date!(2024 - 08 - 25)).unwrap();
+        let overrides = vat_overrides();
+        let vat_codes = VatCodes::new(&overrides, Vec::new());
+
+        let donation = item("Donation", None, "Other", 0.0);
+        let booking = rules
+            .resolve(&donation, &config, &vat_codes, "Intro", &period)
+            .unwrap();
+        assert_eq!(Source::Rule(0), booking.vat_code.source);
+
+        // No rule and no configured code for 6%
+        let book = item("Book", None, "Other", 6.0);
+        assert!(rules
+            .resolve(&book, &config, &vat_codes, "Intro", &period)
+            .is_err());
+    }
+
+    #[test]
+    fn unmatched_item() {
+        let config = event_config(vec![ProductRule {
+            matches: ProductMatch {
+                category: Some(RegexPattern("^Merchandise$".to_string())),
+                ..Default::default()
+            },
+            cost_center: Some(ExactCostCenterCode("MERCH".to_string())),
+            ..Default::default()
+        }]);
+        let rules = Rules::compile(&config.rules).unwrap();
+        let period = Period::range(date!(2024 - 08 - 19), date!(2024 - 08 - 25)).unwrap();
+        let overrides = vat_overrides();
+        let vat_codes = VatCodes::new(&overrides, Vec::new());
+
+        let ticket = item("Ticket", None, "Tickets", 9.0);
+        assert!(rules
+            .resolve(&ticket, &config, &vat_codes, "Intro", &period)
+            .is_err());
+    }
+}
@@ -1,15 +1,24 @@
 use color_eyre::eyre::Error;
-use time::{Duration, OffsetDateTime, Time, UtcOffset, Weekday};
+use time::{Date, Duration, Weekday};
+use time_tz::{timezones, Tz};
 
-/// Determine the last monday.
-/// If today is monday, returns today.
-/// If today is tuesday, returns yesterday.
-/// Time is set to midnight.
-pub fn last_monday(offset: UtcOffset) -> OffsetDateTime {
-    let now = OffsetDateTime::now_utc().to_offset(offset);
+/// The timezone used if none is configured.
+pub const DEFAULT_TIMEZONE: &str = "Europe/Amsterdam";
+
+/// Look up an IANA timezone by name, e.g. `Europe/Amsterdam`.
+///
+/// # Errors
+///
+/// If the timezone does not exist
+pub fn timezone(name: &str) -> color_eyre::Result<&'static Tz> {
+    timezones::get_by_name(name).ok_or(Error::msg(format!("Unknown timezone '{name}'")))
+}
 
-    let date = now.date();
-    let last_monday = match now.weekday() {
+/// Determine the last monday.
+/// If `date` is a monday, returns `date`.
+/// If `date` is a tuesday, returns the day before.
+pub fn last_monday(date: Date) -> Date {
+    match date.weekday() {
         Weekday::Monday => date,
         Weekday::Tuesday => date - Duration::days(1),
         Weekday::Wednesday => date - Duration::days(2),
@@ -17,38 +26,5 @@ pub fn last_monday(offset: UtcOffset) -> OffsetDateTime {
         Weekday::Friday => date - Duration::days(4),
         Weekday::Saturday => date - Duration::days(5),
         Weekday::Sunday => date - Duration::days(6),
-    };
-
-    last_monday.midnight().assume_offset(offset)
-}
-
-/// Return the start and end dates for the Pretix export.
-/// The provided `monday` indicates the start of the export period,
-/// the end date will be the first sunday following the provided monday.
-///
-/// The time of both dates will be midnight.
-///
-/// # Errors
-///
-/// If `monday` is not actually a monday.
-pub fn pretix_export_period(
-    monday: OffsetDateTime,
-    offset: UtcOffset,
-) -> color_eyre::Result<(OffsetDateTime, OffsetDateTime)> {
-    if monday.weekday().ne(&Weekday::Monday) {
-        return Err(Error::msg(format!(
-            "The 'Monday' provided is not actually a monday, but a {}",
-            monday.weekday()
-        )));
     }
-
-    Ok((
-        monday
-            .date()
-            .with_time(Time::MIDNIGHT)
-            .assume_offset(offset),
-        (monday.date() + Duration::days(6))
-            .with_time(Time::from_hms(23, 59, 59).unwrap())
-            .assume_offset(offset),
-    ))
 }
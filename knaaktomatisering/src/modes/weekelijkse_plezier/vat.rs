@@ -0,0 +1,142 @@
+use crate::config::VATCode;
+use crate::modes::weekelijkse_plezier::rules::{Resolved, Source};
+use color_eyre::eyre::Error;
+use color_eyre::Result;
+use exact_request::api::vat_code::{list_vat_codes, VATCode as ExactVATCode};
+use exact_request::ExactClient;
+
+/// Percentages closer together than this are the same tax rate.
+const TAX_RATE_TOLERANCE: f32 = 0.005;
+
+/// The sales VAT codes per tax rate.
+/// Codes configured in `exact.vat_codes` take precedence over the codes in Exact.
+pub struct VatCodes<'a> {
+    overrides: &'a [VATCode],
+    exact: Vec<ExactVATCode>,
+}
+
+impl<'a> VatCodes<'a> {
+    pub fn new(overrides: &'a [VATCode], exact: Vec<ExactVATCode>) -> Self {
+        Self { overrides, exact }
+    }
+
+    /// Fetch the VAT codes from Exact.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails
+    pub async fn fetch(client: &ExactClient, overrides: &'a [VATCode]) -> Result<Self> {
+        Ok(Self::new(overrides, list_vat_codes(client).await?))
+    }
+
+    /// The VAT code to book sales with the tax rate, e.g. `21.0`, with.
+    /// Amounts are booked excluding VAT, so only codes excluding VAT are considered.
+    ///
+    /// # Errors
+    ///
+    /// If no code is configured for the tax rate, and Exact has no or more than one
+    /// unblocked sales code for it
+    pub fn for_tax_rate(&self, tax_rate: f32) -> Result<Resolved<String>> {
+        if let Some(code) = self
+            .overrides
+            .iter()
+            .find(|code| (code.percentage - tax_rate).abs() < TAX_RATE_TOLERANCE)
+        {
+            return Ok(Resolved {
+                value: code.code.clone(),
+                source: Source::VatOverride,
+            });
+        }
+
+        let candidates = self
+            .exact
+            .iter()
+            .filter(|code| !code.is_blocked && code.is_sales() && !code.is_inclusive())
+            .filter(|code| (code.percentage * 100.0 - tax_rate).abs() < TAX_RATE_TOLERANCE)
+            .collect::<Vec<_>>();
+
+        match candidates.as_slice() {
+            [code] => Ok(Resolved {
+                value: code.code.trim().to_string(),
+                source: Source::TaxRate,
+            }),
+            [] => Err(Error::msg(format!(
+                "Exact has no sales VAT code for {tax_rate:.2}%, configure one in 'exact.vat_codes'"
+            ))),
+            codes => Err(Error::msg(format!(
+                "Exact has multiple sales VAT codes for {tax_rate:.2}% ({}), configure the one to use in 'exact.vat_codes'",
+                codes
+                    .iter()
+                    .map(|code| code.code.trim())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use exact_filter::Guid;
+
+    fn exact_code(
+        code: &str,
+        percentage: f32,
+        vat_type: &str,
+        transaction_type: &str,
+    ) -> ExactVATCode {
+        ExactVATCode {
+            id: Guid::new("00000000-0000-0000-0000-000000000000"),
+            code: format!("{code:>3}"),
+            description: code.to_string(),
+            percentage,
+            vat_type: vat_type.to_string(),
+            transaction_type: transaction_type.to_string(),
+            is_blocked: false,
+        }
+    }
+
+    fn exact_codes() -> Vec<ExactVATCode> {
+        let mut blocked = exact_code("6", 0.09, "B", "S");
+        blocked.is_blocked = true;
+
+        vec![
+            exact_code("1", 0.09, "B", "S"),
+            exact_code("2", 0.21, "B", "B"),
+            exact_code("3", 0.21, "I", "S"),
+            exact_code("4", 0.21, "B", "P"),
+            exact_code("0", 0.0, "N", "S"),
+            exact_code("5", 0.0, "B", "S"),
+            blocked,
+        ]
+    }
+
+    #[test]
+    fn sales_code_from_exact() {
+        let vat_codes = VatCodes::new(&[], exact_codes());
+
+        let code = vat_codes.for_tax_rate(21.0).unwrap();
+        assert_eq!("2", code.value);
+        assert_eq!(Source::TaxRate, code.source);
+        assert_eq!("1", vat_codes.for_tax_rate(9.0).unwrap().value);
+
+        // Ambiguous and unknown rates
+        assert!(vat_codes.for_tax_rate(0.0).is_err());
+        assert!(vat_codes.for_tax_rate(6.0).is_err());
+    }
+
+    #[test]
+    fn override_takes_precedence() {
+        let overrides = [VATCode {
+            percentage: 0.0,
+            code: "0".to_string(),
+        }];
+        let vat_codes = VatCodes::new(&overrides, exact_codes());
+
+        let code = vat_codes.for_tax_rate(0.0).unwrap();
+        assert_eq!("0", code.value);
+        assert_eq!(Source::VatOverride, code.source);
+        assert_eq!("2", vat_codes.for_tax_rate(21.0).unwrap().value);
+    }
+}
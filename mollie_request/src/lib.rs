@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::Deref;
+
+use log::trace;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_this_or_that::as_f64;
+
+pub mod payments;
+pub mod settlements;
+
+/// The URL of the Mollie API.
+pub const MOLLIE_API_URL: &str = "https://api.mollie.com";
+
+pub struct MollieClient {
+    client: Client,
+    base_url: String,
+}
+
+impl MollieClient {
+    /// Create a client authenticating with an organization access token.
+    /// API keys can not be used, as they don't have access to settlements.
+    pub fn new<S: AsRef<str>>(access_token: S) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", access_token.as_ref()))
+                .expect("Creating authorization header value"),
+        );
+
+        let client = Client::builder()
+            .default_headers(headers)
+            .user_agent("Sticky Knaaktomatisering")
+            .build()
+            .expect("Creating Mollie request client");
+
+        Self {
+            client,
+            base_url: MOLLIE_API_URL.to_string(),
+        }
+    }
+
+    /// Set the URL of the Mollie API.
+    /// By default, [MOLLIE_API_URL] is used.
+    /// Should *not* end with a slash (`/`).
+    pub fn set_base_url(&mut self, base_url: String) {
+        self.base_url = base_url;
+    }
+
+    /// Format a URL for the Mollie API.
+    /// If the Mollie documentation specifies `/v2/settlements`, pass that to this function.
+    pub fn url<S: AsRef<str>>(&self, path: S) -> String {
+        let url = format!("{}{}", self.base_url, path.as_ref());
+        trace!("URL: {url}");
+        url
+    }
+
+    /// List all values from an endpoint that is paginated, e.g. `/v2/settlements`
+    pub async fn list_paginated<S: AsRef<str>, T: DeserializeOwned>(
+        &self,
+        url: S,
+    ) -> reqwest::Result<Vec<T>> {
+        let mut data = Vec::new();
+        let mut next = Some(url.as_ref().to_string());
+
+        while let Some(next_url) = next {
+            let response: MollieListResponse<T> = self
+                .get(next_url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            next = response.links.next.map(|link| link.href);
+            data.extend(response.embedded.into_values().flatten());
+        }
+
+        Ok(data)
+    }
+}
+
+impl Deref for MollieClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+/// A list of values as returned by Mollie.
+/// The values are embedded under the name of the resource, e.g. `settlements`.
+#[derive(Debug, Deserialize)]
+struct MollieListResponse<T> {
+    #[serde(rename = "_embedded")]
+    embedded: HashMap<String, Vec<T>>,
+    #[serde(rename = "_links")]
+    links: MollieLinks,
+}
+
+#[derive(Debug, Deserialize)]
+struct MollieLinks {
+    next: Option<MollieLink>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MollieLink {
+    href: String,
+}
+
+/// An amount of money.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Amount {
+    /// The currency code, e.g. `EUR`.
+    pub currency: String,
+    #[serde(deserialize_with = "as_f32")]
+    pub value: f32,
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:.2}", self.currency, self.value)
+    }
+}
+
+fn as_f32<'de, D>(deserializer: D) -> Result<f32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(as_f64(deserializer)? as f32)
+}
@@ -0,0 +1,55 @@
+use crate::Amount;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Display;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub id: PaymentId,
+    pub description: String,
+    pub status: PaymentStatus,
+    /// The amount paid by the customer.
+    pub amount: Amount,
+    /// The amount that is settled, in the currency of the settlement.
+    /// Only set for paid payments.
+    pub settlement_amount: Option<Amount>,
+    /// The payment method, e.g. `ideal`.
+    pub method: Option<String>,
+    /// Metadata provided by the application that created the payment.
+    #[serde(default)]
+    pub metadata: Value,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub paid_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct PaymentId(pub String);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Open,
+    Canceled,
+    Pending,
+    Authorized,
+    Expired,
+    Failed,
+    Paid,
+}
+
+impl Payment {
+    /// Deserialize the metadata of the payment.
+    /// Returns `None` if the metadata is absent or has a different format.
+    pub fn metadata<T: DeserializeOwned>(&self) -> Option<T> {
+        serde_json::from_value(self.metadata.clone()).ok()
+    }
+}
+
+impl Display for PaymentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::payments::Payment;
+use crate::{Amount, MollieClient};
+use reqwest::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use time::OffsetDateTime;
+
+/// A payout of Mollie to the bank account.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Settlement {
+    pub id: SettlementId,
+    /// The bank reference of the payout, e.g. `1234567.2404.03`.
+    /// This reference is in the description of the bank transaction.
+    pub reference: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub settled_at: Option<OffsetDateTime>,
+    pub status: SettlementStatus,
+    /// The amount paid out, i.e. the revenue minus the costs.
+    pub amount: Amount,
+    /// The revenue and costs in the settlement.
+    /// The keys are the year and the month of the period, e.g. `2024` and `10`.
+    #[serde(default)]
+    pub periods: HashMap<String, HashMap<String, SettlementPeriod>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SettlementId(pub String);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettlementStatus {
+    Open,
+    Pending,
+    Paidout,
+    Failed,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementPeriod {
+    #[serde(default)]
+    pub revenue: Vec<SettlementRevenue>,
+    #[serde(default)]
+    pub costs: Vec<SettlementCost>,
+    pub invoice_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementRevenue {
+    pub description: String,
+    pub method: Option<String>,
+    pub count: u32,
+    pub amount_net: Amount,
+    pub amount_vat: Option<Amount>,
+    pub amount_gross: Amount,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementCost {
+    pub description: String,
+    pub method: Option<String>,
+    pub count: u32,
+    pub amount_net: Amount,
+    pub amount_vat: Option<Amount>,
+    pub amount_gross: Amount,
+}
+
+impl Settlement {
+    /// Get a settlement by its ID (`stl_...`) or its bank reference.
+    pub async fn get(client: &MollieClient, id_or_reference: &str) -> Result<Self> {
+        client
+            .get(client.url(format!("/v2/settlements/{id_or_reference}")))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// List all settlements, most recent first.
+    pub async fn list(client: &MollieClient) -> Result<Vec<Self>> {
+        client.list_paginated(client.url("/v2/settlements")).await
+    }
+
+    /// List the payments included in the settlement.
+    pub async fn payments(&self, client: &MollieClient) -> Result<Vec<Payment>> {
+        client
+            .list_paginated(client.url(format!("/v2/settlements/{}/payments", self.id)))
+            .await
+    }
+
+    /// The revenue in all periods of the settlement.
+    pub fn revenue(&self) -> impl Iterator<Item = &SettlementRevenue> {
+        self.periods
+            .values()
+            .flat_map(|months| months.values())
+            .flat_map(|period| &period.revenue)
+    }
+
+    /// The costs in all periods of the settlement.
+    pub fn costs(&self) -> impl Iterator<Item = &SettlementCost> {
+        self.periods
+            .values()
+            .flat_map(|months| months.values())
+            .flat_map(|period| &period.costs)
+    }
+}
+
+impl Display for SettlementId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
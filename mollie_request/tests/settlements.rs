@@ -0,0 +1,173 @@
+use mollie_request::payments::PaymentStatus;
+use mollie_request::settlements::{Settlement, SettlementStatus};
+use mollie_request::MollieClient;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client(server: &MockServer) -> MollieClient {
+    let mut client = MollieClient::new("access_test");
+    client.set_base_url(server.uri());
+    client
+}
+
+fn settlement() -> Value {
+    json!({
+        "resource": "settlement",
+        "id": "stl_jDk30akdN",
+        "reference": "1234567.2410.03",
+        "createdAt": "2024-10-21T08:00:00+00:00",
+        "settledAt": "2024-10-22T10:00:00+00:00",
+        "status": "paidout",
+        "amount": { "value": "39.75", "currency": "EUR" },
+        "periods": {
+            "2024": {
+                "10": {
+                    "revenue": [{
+                        "description": "iDEAL",
+                        "method": "ideal",
+                        "count": 2,
+                        "amountNet": { "value": "40.00", "currency": "EUR" },
+                        "amountVat": null,
+                        "amountGross": { "value": "40.00", "currency": "EUR" }
+                    }],
+                    "costs": [{
+                        "description": "iDEAL",
+                        "method": "ideal",
+                        "count": 2,
+                        "rate": { "fixed": { "value": "0.125", "currency": "EUR" } },
+                        "amountNet": { "value": "0.21", "currency": "EUR" },
+                        "amountVat": { "value": "0.04", "currency": "EUR" },
+                        "amountGross": { "value": "0.25", "currency": "EUR" }
+                    }],
+                    "invoiceId": "inv_FrvewDA3Pr"
+                }
+            }
+        },
+        "_links": {}
+    })
+}
+
+fn payment(id: &str, value: &str, metadata: Value) -> Value {
+    json!({
+        "resource": "payment",
+        "id": id,
+        "mode": "live",
+        "createdAt": "2024-10-14T12:00:00+00:00",
+        "status": "paid",
+        "paidAt": "2024-10-14T12:01:00+00:00",
+        "amount": { "value": value, "currency": "EUR" },
+        "settlementAmount": { "value": value, "currency": "EUR" },
+        "description": "Order ABC12",
+        "method": "ideal",
+        "metadata": metadata,
+        "settlementId": "stl_jDk30akdN"
+    })
+}
+
+#[tokio::test]
+async fn get_settlement_by_reference() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v2/settlements/1234567.2410.03"))
+        .and(header("Authorization", "Bearer access_test"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(settlement()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let settlement = Settlement::get(&client(&server), "1234567.2410.03")
+        .await
+        .unwrap();
+
+    assert_eq!("stl_jDk30akdN", settlement.id.0);
+    assert_eq!(SettlementStatus::Paidout, settlement.status);
+    assert_eq!(39.75, settlement.amount.value);
+    assert_eq!(
+        40.0,
+        settlement
+            .revenue()
+            .map(|r| r.amount_gross.value)
+            .sum::<f32>()
+    );
+    assert_eq!(
+        0.25,
+        settlement
+            .costs()
+            .map(|c| c.amount_gross.value)
+            .sum::<f32>()
+    );
+}
+
+#[tokio::test]
+async fn unknown_settlement() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v2/settlements/stl_unknown"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "status": 404,
+            "title": "Not Found",
+            "detail": "No settlement exists with token stl_unknown."
+        })))
+        .mount(&server)
+        .await;
+
+    let error = Settlement::get(&client(&server), "stl_unknown")
+        .await
+        .unwrap_err();
+    assert_eq!(Some(reqwest::StatusCode::NOT_FOUND), error.status());
+}
+
+#[tokio::test]
+async fn list_settlement_payments_paginated() {
+    #[derive(Deserialize)]
+    struct Metadata {
+        order: String,
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v2/settlements/stl_jDk30akdN"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(settlement()))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/settlements/stl_jDk30akdN/payments"))
+        .and(query_param("from", "tr_second"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "count": 1,
+            "_embedded": { "payments": [payment("tr_second", "15.00", Value::Null)] },
+            "_links": { "next": null }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/settlements/stl_jDk30akdN/payments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "count": 1,
+            "_embedded": { "payments": [payment("tr_first", "25.00", json!({ "order": "ABC12" }))] },
+            "_links": {
+                "next": {
+                    "href": format!("{}/v2/settlements/stl_jDk30akdN/payments?from=tr_second", server.uri()),
+                    "type": "application/hal+json"
+                }
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let client = client(&server);
+    let settlement = Settlement::get(&client, "stl_jDk30akdN").await.unwrap();
+    let payments = settlement.payments(&client).await.unwrap();
+
+    assert_eq!(2, payments.len());
+    assert_eq!("tr_first", payments[0].id.0);
+    assert_eq!(PaymentStatus::Paid, payments[0].status);
+    assert_eq!(
+        Some("ABC12".to_string()),
+        payments[0].metadata::<Metadata>().map(|m| m.order)
+    );
+    assert_eq!("tr_second", payments[1].id.0);
+    assert!(payments[1].metadata::<Metadata>().is_none());
+}
@@ -6,24 +6,65 @@ use reqwest::{Response, Result, StatusCode};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_this_or_that::as_f64;
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 use thiserror::Error;
-use time::OffsetDateTime;
+use time::format_description::well_known::Rfc2822;
+use time::{Date, OffsetDateTime};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct DataExporter {
     pub identifier: String,
     pub verbose_name: String,
     pub input_parameters: Vec<DataExporterInput>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct DataExporterInput {
     pub name: String,
     pub required: bool,
     pub choices: Option<Vec<String>>,
 }
 
+/// The value of an exporter input parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum ExportParameterValue {
+    Bool(bool),
+    String(String),
+    List(Vec<String>),
+}
+
+/// How exports are polled until they are complete.
+#[derive(Debug, Clone)]
+pub struct ExportPolling {
+    /// The maximum total time to wait for a single export.
+    pub deadline: Duration,
+    /// The interval between the first polls.
+    /// Doubled after every poll, up to `max_interval`.
+    pub initial_interval: Duration,
+    /// The maximum interval between polls.
+    pub max_interval: Duration,
+}
+
+impl Default for ExportPolling {
+    fn default() -> Self {
+        Self {
+            deadline: Duration::from_secs(10 * 60),
+            initial_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Input parameters for an exporter.
+/// Key is the name of the parameter as listed in [DataExporter::input_parameters].
+pub type ExportParameters = HashMap<String, ExportParameterValue>;
+
 #[derive(Debug, Deserialize)]
 pub struct OrderDataExportOrderItem {
     pub fees: Vec<OrderDataExportOrderItemFee>,
@@ -33,19 +74,113 @@ pub struct OrderDataExportOrderItem {
     pub total: f32,
     #[serde(rename = "positions")]
     pub ordered_items: Vec<OrderDataExportOrderedItem>,
+    #[serde(default)]
+    pub payments: Vec<OrderDataExportPayment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderDataExportPayment {
+    pub state: String,
+    pub provider: String,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub payment_date: Option<OffsetDateTime>,
+}
+
+impl OrderDataExportPayment {
+    /// Whether the payment was successful.
+    /// Refunded payments were successful at the time of payment.
+    pub fn is_successful(&self) -> bool {
+        self.state.eq("confirmed") || self.state.eq("refunded")
+    }
+}
+
+impl OrderDataExportOrderItem {
+    /// The date of the order on the provided date axis.
+    /// `None` if the order has no successful payment and the axis is a payment date.
+    pub fn date(&self, date_axis: DateAxis) -> Option<OffsetDateTime> {
+        let payment_dates = self
+            .payments
+            .iter()
+            .filter(|payment| payment.is_successful())
+            .filter_map(|payment| payment.payment_date);
+
+        match date_axis {
+            DateAxis::OrderDate => Some(self.datetime),
+            DateAxis::LastPaymentDate => payment_dates.max(),
+        }
+    }
+}
+
+/// The date on which orders are placed in a period.
+///
+/// These are the choices of the `date_axis` parameter of Pretix' `pdfreport` exporter,
+/// see `OverviewReport` in `pretix/plugins/reports/exporters.py`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateAxis {
+    /// The date the order was placed.
+    OrderDate,
+    /// The date of the last successful payment.
+    LastPaymentDate,
+}
+
+impl DateAxis {
+    /// The value of the `date_axis` exporter parameter.
+    pub fn as_parameter(&self) -> &'static str {
+        match self {
+            Self::OrderDate => "order_date",
+            Self::LastPaymentDate => "last_payment_date",
+        }
+    }
+}
+
+impl Display for DateAxis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_parameter())
+    }
+}
+
+impl FromStr for DateAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "order_date" => Ok(Self::OrderDate),
+            "last_payment_date" => Ok(Self::LastPaymentDate),
+            _ => Err(format!(
+                "Invalid date axis '{s}', expected one of order_date, last_payment_date"
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OrderDataExportOrderedItem {
     pub item: u32,
+    /// The ID of the variation of the item, if the item has variations
+    pub variation: Option<u32>,
+    /// The price including VAT
     #[serde(deserialize_with = "as_f32")]
     pub price: f32,
+    /// The VAT percentage of the position
+    #[serde(default, deserialize_with = "as_option_f32")]
+    pub tax_rate: Option<f32>,
+    /// The VAT included in the price
+    #[serde(default, deserialize_with = "as_option_f32")]
+    pub tax_value: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OrderDataExportOrderItemFee {
+    /// The value including VAT
     #[serde(deserialize_with = "as_f32")]
     pub value: f32,
+    /// The VAT percentage of the fee
+    #[serde(default, deserialize_with = "as_option_f32")]
+    pub tax_rate: Option<f32>,
+    /// The VAT included in the value
+    #[serde(default, deserialize_with = "as_option_f32")]
+    pub tax_value: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -54,12 +189,39 @@ pub struct ExportResponseSaleItem {
     pub name: String,
     #[serde(deserialize_with = "as_f32")]
     pub tax_rate: f32,
+    /// The ID of the category of the item
+    pub category: Option<u32>,
+    #[serde(default)]
+    pub variations: Vec<ExportResponseSaleItemVariation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportResponseSaleItemVariation {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportResponseCategory {
+    pub id: u32,
+    pub name: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OrderExport {
     pub orders: Vec<OrderDataExportOrderItem>,
     pub items: Vec<ExportResponseSaleItem>,
+    #[serde(default)]
+    pub categories: Vec<ExportResponseCategory>,
+}
+
+/// The order data of one event.
+/// The slug is part of the `event` object of Pretix' JSON export.
+#[derive(Debug, Deserialize)]
+pub struct EventOrderExport {
+    pub slug: EventId,
+    #[serde(flatten)]
+    pub export: OrderExport,
 }
 
 #[derive(Debug, Error)]
@@ -72,8 +234,32 @@ pub enum ExporterError {
     Format(#[from] time::error::Format),
     #[error("Export failed: {reason}")]
     ExportFail { reason: String },
-    #[error("Export failed for unknown reason: HTTP {status}")]
-    Other { status: StatusCode },
+    #[error("Export failed with unexpected response: HTTP {status}: {body}")]
+    UnexpectedResponse { status: StatusCode, body: String },
+    #[error("Export did not complete within {elapsed:.2?}. Last response: HTTP {status}")]
+    Timeout {
+        elapsed: Duration,
+        status: StatusCode,
+    },
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid export: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Exporter '{identifier}' does not exist. Available exporters: {}", available.join(", "))]
+    UnknownExporter {
+        identifier: String,
+        available: Vec<String>,
+    },
+    #[error("Missing required parameter '{name}' for exporter '{identifier}'")]
+    MissingParameter { identifier: String, name: String },
+    #[error("Exporter '{identifier}' has no parameter '{name}'")]
+    UnknownParameter { identifier: String, name: String },
+    #[error("Invalid value '{value}' for parameter '{name}'. Allowed values: {}", choices.join(", "))]
+    InvalidChoice {
+        name: String,
+        value: String,
+        choices: Vec<String>,
+    },
 }
 
 impl DataExporter {
@@ -83,19 +269,216 @@ impl DataExporter {
         event: &EventId,
     ) -> Result<Vec<Self>> {
         client
-            .list_paginated(client.url(format!(
-                "/api/v1/organizers/{organizer}/events/{event}/exporters"
-            )))
+            .list_paginated(client.url(format!("{}/exporters", Self::event_path(organizer, event))))
+            .await
+    }
+
+    /// List the organizer-level exporters.
+    /// These exporters can cover multiple events of the organizer in one export.
+    pub async fn list_organizer(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+    ) -> Result<Vec<Self>> {
+        client
+            .list_paginated(client.url(format!("{}/exporters", Self::organizer_path(organizer))))
             .await
     }
 
+    /// Validate the provided parameters against the input parameters of this exporter.
+    ///
+    /// # Errors
+    ///
+    /// - If a required parameter is missing
+    /// - If a parameter is provided that the exporter does not know
+    /// - If a value is not one of the allowed choices of the parameter
+    pub fn validate(
+        &self,
+        parameters: &ExportParameters,
+    ) -> std::result::Result<(), ExporterError> {
+        for input in &self.input_parameters {
+            if input.required && !parameters.contains_key(&input.name) {
+                return Err(ExporterError::MissingParameter {
+                    identifier: self.identifier.clone(),
+                    name: input.name.clone(),
+                });
+            }
+        }
+
+        for (name, value) in parameters {
+            let input = self
+                .input_parameters
+                .iter()
+                .find(|input| input.name.eq(name))
+                .ok_or_else(|| ExporterError::UnknownParameter {
+                    identifier: self.identifier.clone(),
+                    name: name.clone(),
+                })?;
+
+            let values = match value {
+                ExportParameterValue::String(v) => vec![v],
+                ExportParameterValue::List(v) => v.iter().collect(),
+                ExportParameterValue::Bool(_) => vec![],
+            };
+
+            for value in values {
+                Self::validate_choice(input, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate that `value` is one of the choices of the input parameter `name`.
+    ///
+    /// # Errors
+    ///
+    /// - If the exporter has no parameter `name`
+    /// - If the value is not one of the allowed choices
+    pub fn validate_parameter(
+        &self,
+        name: &str,
+        value: &str,
+    ) -> std::result::Result<(), ExporterError> {
+        let input = self
+            .input_parameters
+            .iter()
+            .find(|input| input.name.eq(name))
+            .ok_or_else(|| ExporterError::UnknownParameter {
+                identifier: self.identifier.clone(),
+                name: name.to_string(),
+            })?;
+
+        Self::validate_choice(input, value)
+    }
+
+    fn validate_choice(
+        input: &DataExporterInput,
+        value: &str,
+    ) -> std::result::Result<(), ExporterError> {
+        match &input.choices {
+            Some(choices) if !choices.iter().any(|choice| choice.eq(value)) => {
+                Err(ExporterError::InvalidChoice {
+                    name: input.name.clone(),
+                    value: value.to_string(),
+                    choices: choices.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run any exporter of an event.
+    /// The parameters are validated against the input parameters reported by Pretix before the exporter is started.
+    ///
+    /// Returns the download URL of the export. Use [Self::download_to_file] to retrieve the result.
+    ///
+    /// # Errors
+    ///
+    /// - If the exporter does not exist
+    /// - If the parameters are invalid, see [Self::validate]
+    /// - If the request fails
+    pub async fn run(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        event: &EventId,
+        exporter_identifier: &str,
+        parameters: &ExportParameters,
+    ) -> std::result::Result<String, ExporterError> {
+        let exporters = Self::list(client, organizer, event).await?;
+        Self::find(&exporters, exporter_identifier)?.validate(parameters)?;
+
+        Ok(Self::run_exporter(
+            client,
+            &Self::event_path(organizer, event),
+            exporter_identifier,
+            Some(parameters),
+        )
+        .await?)
+    }
+
+    /// Run any organizer-level exporter.
+    /// The parameters are validated against the input parameters reported by Pretix before the exporter is started.
+    /// Which events are covered by the export is controlled with the `events` or `all_events` parameters.
+    ///
+    /// Returns the download URL of the export. Use [Self::download_to_file] to retrieve the result.
+    ///
+    /// # Errors
+    ///
+    /// - If the exporter does not exist
+    /// - If the parameters are invalid, see [Self::validate]
+    /// - If the request fails
+    pub async fn run_organizer(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        exporter_identifier: &str,
+        parameters: &ExportParameters,
+    ) -> std::result::Result<String, ExporterError> {
+        let exporters = Self::list_organizer(client, organizer).await?;
+        Self::find(&exporters, exporter_identifier)?.validate(parameters)?;
+
+        Ok(Self::run_exporter(
+            client,
+            &Self::organizer_path(organizer),
+            exporter_identifier,
+            Some(parameters),
+        )
+        .await?)
+    }
+
+    /// Find the exporter with the provided identifier.
+    fn find<'a>(
+        exporters: &'a [Self],
+        exporter_identifier: &str,
+    ) -> std::result::Result<&'a Self, ExporterError> {
+        exporters
+            .iter()
+            .find(|exporter| exporter.identifier.eq(exporter_identifier))
+            .ok_or_else(|| ExporterError::UnknownExporter {
+                identifier: exporter_identifier.to_string(),
+                available: exporters
+                    .iter()
+                    .map(|exporter| exporter.identifier.clone())
+                    .collect(),
+            })
+    }
+
+    /// Wait for the export at `url` to complete and write the result to `path`.
+    /// The result is streamed to disk, rather than loaded into memory as a whole.
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// - If the export fails
+    /// - If the file could not be written
+    pub async fn download_to_file<S: AsRef<str>, P: AsRef<Path>>(
+        client: &PretixClient,
+        url: S,
+        path: P,
+    ) -> std::result::Result<u64, ExporterError> {
+        let mut response = Self::wait_for_export(client, url).await?;
+
+        let mut file = fs::File::create(path.as_ref()).await?;
+        let mut written = 0;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+
+        debug!("Wrote {written} bytes to {}", path.as_ref().display());
+        Ok(written)
+    }
+
     pub async fn export_order_data(
         client: &PretixClient,
         organizer: &OrganizerId,
         event: &EventId,
     ) -> std::result::Result<OrderExport, ExporterError> {
         // Start the export
-        let url = Self::run_exporter::<()>(client, organizer, event, "json", None).await?;
+        let url =
+            Self::run_exporter::<()>(client, &Self::event_path(organizer, event), "json", None)
+                .await?;
 
         // Format of the response body
         #[derive(Deserialize)]
@@ -111,28 +494,26 @@ impl DataExporter {
         Ok(payload.event)
     }
 
+    /// Export the PDF report of an event.
+    /// The report covers the orders with a date on `date_axis` from `from` up to and including `until`.
     pub async fn export_order_data_pdf(
         client: &PretixClient,
         organizer: &OrganizerId,
         event: &EventId,
-        from: OffsetDateTime,
-        until: OffsetDateTime,
+        from: Date,
+        until: Date,
+        date_axis: DateAxis,
     ) -> std::result::Result<Vec<u8>, ExporterError> {
-        let formatter = time::format_description::parse("[year]-[month]-[day]")?;
-        let from = from.format(&formatter)?;
-        let until = until.format(&formatter)?;
+        // Make sure the report supports the date axis
+        Self::find(&Self::list(client, organizer, event).await?, "pdfreport")?
+            .validate_parameter("date_axis", date_axis.as_parameter())?;
 
         // Start the export
         let url = Self::run_exporter(
             client,
-            organizer,
-            event,
+            &Self::event_path(organizer, event),
             "pdfreport",
-            Some(HashMap::from([
-                ("date_axis", "last_payment_date"),
-                ("date_from", &from),
-                ("date_until", &until),
-            ])),
+            Some(Self::report_parameters(from, until, date_axis)?),
         )
         .await?;
 
@@ -141,22 +522,147 @@ impl DataExporter {
         Ok(export.bytes().await?.to_vec())
     }
 
+    /// Export the order data of multiple events of an organizer in one job.
+    /// Each event in the result can be processed as if it were exported with [Self::export_order_data].
+    ///
+    /// Pretix' JSON exporter is an event exporter. If the organizer does not offer it for multiple events,
+    /// or its export lacks a requested event, those events are exported one by one.
+    pub async fn export_organizer_order_data(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        events: &[EventId],
+    ) -> std::result::Result<Vec<EventOrderExport>, ExporterError> {
+        let exporters = Self::list_organizer(client, organizer).await?;
+        let mut exports = if Self::find(&exporters, "json").is_ok() {
+            // Start the export
+            let url = Self::run_exporter(
+                client,
+                &Self::organizer_path(organizer),
+                "json",
+                Some(Self::events_parameters(events)),
+            )
+            .await?;
+
+            // Deserialize once it's done
+            let body = Self::wait_for_export(client, url).await?.bytes().await?;
+            parse_order_exports(&body)?
+        } else {
+            debug!("Organizer {organizer} has no JSON exporter for multiple events");
+            Vec::new()
+        };
+
+        for event in events {
+            if !exports.iter().any(|export| export.slug.eq(event)) {
+                debug!("Exporting the order data of event {event} separately");
+                exports.push(EventOrderExport {
+                    slug: event.clone(),
+                    export: Self::export_order_data(client, organizer, event).await?,
+                });
+            }
+        }
+
+        Ok(exports)
+    }
+
+    /// Export the PDF report of multiple events of an organizer in one job.
+    /// The report covers the orders with a date on `date_axis` from `from` up to and including `until`.
+    /// It is a single report with the totals of all `events`, use [Self::export_order_data_pdf] for a report per event.
+    pub async fn export_organizer_order_data_pdf(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        events: &[EventId],
+        from: Date,
+        until: Date,
+        date_axis: DateAxis,
+    ) -> std::result::Result<Vec<u8>, ExporterError> {
+        // Make sure the report supports the date axis
+        Self::find(&Self::list_organizer(client, organizer).await?, "pdfreport")?
+            .validate_parameter("date_axis", date_axis.as_parameter())?;
+
+        let mut parameters = Self::events_parameters(events);
+        parameters.extend(Self::report_parameters(from, until, date_axis)?);
+
+        // Start the export
+        let url = Self::run_exporter(
+            client,
+            &Self::organizer_path(organizer),
+            "pdfreport",
+            Some(parameters),
+        )
+        .await?;
+
+        // Wait for export completion
+        let export = Self::wait_for_export(client, url).await?;
+        Ok(export.bytes().await?.to_vec())
+    }
+
+    /// Parameters selecting the period covered by a report.
+    /// Pretix interprets the dates in the timezone of the event.
+    fn report_parameters(
+        from: Date,
+        until: Date,
+        date_axis: DateAxis,
+    ) -> std::result::Result<ExportParameters, ExporterError> {
+        let formatter = time::format_description::parse("[year]-[month]-[day]")?;
+
+        Ok(HashMap::from([
+            (
+                "date_axis".to_string(),
+                ExportParameterValue::String(date_axis.as_parameter().to_string()),
+            ),
+            (
+                "date_from".to_string(),
+                ExportParameterValue::String(from.format(&formatter)?),
+            ),
+            (
+                "date_until".to_string(),
+                ExportParameterValue::String(until.format(&formatter)?),
+            ),
+        ]))
+    }
+
+    /// Parameters selecting the events covered by an organizer-level export.
+    fn events_parameters(events: &[EventId]) -> ExportParameters {
+        HashMap::from([(
+            "events".to_string(),
+            ExportParameterValue::List(events.iter().map(|event| event.to_string()).collect()),
+        )])
+    }
+
+    fn event_path(organizer: &OrganizerId, event: &EventId) -> String {
+        format!("/api/v1/organizers/{organizer}/events/{event}")
+    }
+
+    fn organizer_path(organizer: &OrganizerId) -> String {
+        format!("/api/v1/organizers/{organizer}")
+    }
+
+    /// Wait for an export to complete.
+    ///
+    /// The export is polled with exponential backoff, as configured with [PretixClient::set_export_polling].
+    /// A `Retry-After` header sent by Pretix takes precedence over the backoff interval.
+    /// Pending exports (`409`), rate limiting (`429`) and server errors (`5xx`) are retried.
+    ///
+    /// To cancel waiting, drop the returned future.
+    ///
+    /// # Errors
+    ///
+    /// - If the export failed
+    /// - If the export did not complete before the deadline
+    /// - If Pretix responds with any other status
     async fn wait_for_export<S: AsRef<str>>(
         client: &PretixClient,
         url: S,
     ) -> std::result::Result<Response, ExporterError> {
         debug!("Waiting for export {} to be ready", url.as_ref());
+        let polling = client.export_polling();
         let start = Instant::now();
+        let mut interval = polling.initial_interval;
 
         loop {
             let response = client.get(url.as_ref()).send().await?;
 
             match response.status() {
-                StatusCode::CONFLICT => {
-                    // Request is pending
-                    debug!("Waiting on export. ({:.2?})", start.elapsed());
-                    tokio::time::sleep(Duration::from_secs(1)).await;
-                }
                 StatusCode::OK => {
                     // Request is done
                     debug!("Export complete. (took {:.2?})", start.elapsed());
@@ -175,32 +681,53 @@ impl DataExporter {
                         reason: body.message,
                     });
                 }
-                _ => {
-                    return Err(ExporterError::Other {
-                        status: response.status(),
-                    })
+                status
+                    if status == StatusCode::CONFLICT
+                        || status == StatusCode::TOO_MANY_REQUESTS
+                        || status.is_server_error() =>
+                {
+                    // Request is pending, or we should try again later
+                    let wait = retry_after(&response).unwrap_or(interval);
+                    if start.elapsed() + wait > polling.deadline {
+                        return Err(ExporterError::Timeout {
+                            elapsed: start.elapsed(),
+                            status,
+                        });
+                    }
+
+                    debug!(
+                        "Waiting on export, HTTP {status}. Retrying in {wait:.2?} ({:.2?})",
+                        start.elapsed()
+                    );
+                    tokio::time::sleep(wait).await;
+                    interval = (interval * 2).min(polling.max_interval);
+                }
+                status => {
+                    let body = response.text().await?;
+                    error!("Export failed with HTTP {status}: {body}");
+
+                    return Err(ExporterError::UnexpectedResponse { status, body });
                 }
             }
         }
     }
 
+    /// Start an exporter. `base_path` is the path of the organizer or event the exporter belongs to.
     async fn run_exporter<T: Serialize>(
         client: &PretixClient,
-        organizer: &OrganizerId,
-        event: &EventId,
+        base_path: &str,
         exporter_identifier: &str,
         parameters: Option<T>,
     ) -> Result<String> {
-        debug!("Running exporter {organizer}/{event}/{exporter_identifier}/");
+        debug!("Running exporter {base_path}/exporters/{exporter_identifier}/");
 
         #[derive(Deserialize)]
         struct ResponseBody {
             download: String,
         }
 
-        let mut builder = client.post(client.url(format!(
-            "/api/v1/organizers/{organizer}/events/{event}/exporters/{exporter_identifier}/run/"
-        )));
+        let mut builder =
+            client.post(client.url(format!("{base_path}/exporters/{exporter_identifier}/run/")));
 
         if let Some(params) = parameters {
             builder = builder.json(&params);
@@ -212,9 +739,344 @@ impl DataExporter {
     }
 }
 
+/// Parse the result of a JSON export.
+/// An export of a single event contains an `event` object, an export of multiple events an `events` array.
+fn parse_order_exports(body: &[u8]) -> serde_json::Result<Vec<EventOrderExport>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ExportResponse {
+        Events { events: Vec<EventOrderExport> },
+        Event { event: EventOrderExport },
+    }
+
+    Ok(match serde_json::from_slice(body)? {
+        ExportResponse::Events { events } => events,
+        ExportResponse::Event { event } => vec![event],
+    })
+}
+
+/// Read the `Retry-After` header of a response.
+/// Both the delay-seconds and the HTTP-date form are supported.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+
+    parse_retry_after(value, OffsetDateTime::now_utc())
+}
+
+/// Parse the value of a `Retry-After` header into the time to wait from `now`.
+/// A date in the past means no waiting.
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = OffsetDateTime::parse(value.trim(), &Rfc2822).ok()?;
+    Some((date - now).try_into().unwrap_or(Duration::ZERO))
+}
+
 pub fn as_f32<'de, D>(deserializer: D) -> std::result::Result<f32, D::Error>
 where
     D: Deserializer<'de>,
 {
     Ok(as_f64(deserializer)? as f32)
 }
+
+pub fn as_option_f32<'de, D>(deserializer: D) -> std::result::Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Value(#[serde(deserialize_with = "as_f32")] f32);
+
+    Ok(Option::<Value>::deserialize(deserializer)?.map(|Value(value)| value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::datetime;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn exporter() -> DataExporter {
+        DataExporter {
+            identifier: "pdfreport".to_string(),
+            verbose_name: "Sales report".to_string(),
+            input_parameters: vec![
+                DataExporterInput {
+                    name: "date_axis".to_string(),
+                    required: true,
+                    choices: Some(vec![
+                        "order_date".to_string(),
+                        "last_payment_date".to_string(),
+                    ]),
+                },
+                DataExporterInput {
+                    name: "items".to_string(),
+                    required: false,
+                    choices: Some(vec!["1".to_string(), "2".to_string()]),
+                },
+                DataExporterInput {
+                    name: "include_payment_amounts".to_string(),
+                    required: false,
+                    choices: None,
+                },
+            ],
+        }
+    }
+
+    fn string(value: &str) -> ExportParameterValue {
+        ExportParameterValue::String(value.to_string())
+    }
+
+    #[test]
+    fn valid_parameters() {
+        let parameters = HashMap::from([
+            ("date_axis".to_string(), string("order_date")),
+            (
+                "items".to_string(),
+                ExportParameterValue::List(vec!["1".to_string(), "2".to_string()]),
+            ),
+            (
+                "include_payment_amounts".to_string(),
+                ExportParameterValue::Bool(true),
+            ),
+        ]);
+
+        assert!(exporter().validate(&parameters).is_ok());
+    }
+
+    #[test]
+    fn missing_required_parameter() {
+        let error = exporter().validate(&HashMap::new()).unwrap_err();
+        assert!(matches!(
+            error,
+            ExporterError::MissingParameter { ref name, .. } if name == "date_axis"
+        ));
+    }
+
+    #[test]
+    fn unknown_parameter() {
+        let parameters = HashMap::from([
+            ("date_axis".to_string(), string("order_date")),
+            ("colour".to_string(), string("blue")),
+        ]);
+
+        let error = exporter().validate(&parameters).unwrap_err();
+        assert!(matches!(
+            error,
+            ExporterError::UnknownParameter { ref name, .. } if name == "colour"
+        ));
+    }
+
+    #[test]
+    fn invalid_choice() {
+        let parameters = HashMap::from([("date_axis".to_string(), string("payment_date"))]);
+        assert!(matches!(
+            exporter().validate(&parameters).unwrap_err(),
+            ExporterError::InvalidChoice { ref value, .. } if value == "payment_date"
+        ));
+
+        // Every value of a list is checked
+        let parameters = HashMap::from([
+            ("date_axis".to_string(), string("order_date")),
+            (
+                "items".to_string(),
+                ExportParameterValue::List(vec!["1".to_string(), "3".to_string()]),
+            ),
+        ]);
+        assert!(matches!(
+            exporter().validate(&parameters).unwrap_err(),
+            ExporterError::InvalidChoice { ref value, .. } if value == "3"
+        ));
+    }
+
+    /// The shape of Pretix' JSON export of a single event, trimmed to one order.
+    const EVENT_EXPORT: &str = r#"{
+        "event": {
+            "name": "Intro 2024",
+            "slug": "intro-2024",
+            "organizer": {"name": "Sticky", "slug": "sticky"},
+            "categories": [{"id": 1, "name": "Tickets", "internal_name": null}],
+            "items": [{
+                "id": 10, "name": "Ticket", "internal_name": null, "category": 1,
+                "price": "12.99", "tax_rate": "21.00", "tax_name": "BTW",
+                "admission": true, "active": true, "variations": []
+            }],
+            "questions": [],
+            "orders": [{
+                "code": "ABC12", "status": "p", "user": "lid@svsticky.nl",
+                "datetime": "2024-10-14T12:00:00+02:00",
+                "fees": [{"type": "payment", "value": "0.30", "tax_rate": "0.00", "tax_value": "0.00"}],
+                "total": "13.29",
+                "positions": [{
+                    "id": 1, "item": 10, "variation": null,
+                    "price": "12.99", "tax_rate": "21.00", "tax_value": "2.25"
+                }],
+                "payments": [{
+                    "amount": "13.29", "state": "confirmed", "provider": "mollie",
+                    "payment_date": "2024-10-14T12:01:00+02:00"
+                }]
+            }],
+            "quotas": []
+        }
+    }"#;
+
+    #[test]
+    fn parse_single_event_export() {
+        let exports = parse_order_exports(EVENT_EXPORT.as_bytes()).unwrap();
+        assert_eq!(1, exports.len());
+        assert_eq!("intro-2024", exports[0].slug.to_string());
+
+        let order = &exports[0].export.orders[0];
+        assert_eq!(13.29, order.total);
+        assert_eq!(Some(2.25), order.ordered_items[0].tax_value);
+        assert_eq!("Tickets", exports[0].export.categories[0].name);
+    }
+
+    #[test]
+    fn split_multi_event_export() {
+        let event =
+            serde_json::from_str::<serde_json::Value>(EVENT_EXPORT).unwrap()["event"].clone();
+        let mut other = event.clone();
+        other["slug"] = "gala-2024".into();
+        other["orders"] = serde_json::json!([]);
+        let body = serde_json::json!({ "events": [event, other] }).to_string();
+
+        let exports = parse_order_exports(body.as_bytes()).unwrap();
+        assert_eq!(
+            vec!["intro-2024", "gala-2024"],
+            exports
+                .iter()
+                .map(|export| export.slug.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, exports[0].export.orders.len());
+        assert!(exports[1].export.orders.is_empty());
+    }
+
+    #[test]
+    fn validate_single_parameter() {
+        let exporter = exporter();
+        assert!(exporter
+            .validate_parameter("date_axis", "last_payment_date")
+            .is_ok());
+        assert!(exporter
+            .validate_parameter("date_axis", "payment_date")
+            .is_err());
+        assert!(exporter.validate_parameter("event_date", "x").is_err());
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let now = datetime!(2024-10-21 12:00 UTC);
+        assert_eq!(Some(Duration::from_secs(5)), parse_retry_after("5", now));
+        assert_eq!(
+            Some(Duration::from_secs(120)),
+            parse_retry_after(" 120 ", now)
+        );
+    }
+
+    #[test]
+    fn retry_after_date() {
+        let now = datetime!(2024-10-21 12:00 UTC);
+        assert_eq!(
+            Some(Duration::from_secs(90)),
+            parse_retry_after("Mon, 21 Oct 2024 12:01:30 +0000", now)
+        );
+        assert_eq!(
+            Some(Duration::ZERO),
+            parse_retry_after("Mon, 21 Oct 2024 11:00:00 +0000", now)
+        );
+        assert_eq!(None, parse_retry_after("soon", now));
+    }
+
+    fn client(server: &MockServer, deadline: Duration) -> PretixClient {
+        let mut client = PretixClient::new("token", server.uri());
+        client.set_export_polling(ExportPolling {
+            deadline,
+            initial_interval: Duration::from_millis(10),
+            max_interval: Duration::from_millis(40),
+        });
+        client
+    }
+
+    #[tokio::test]
+    async fn export_times_out_at_deadline() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/download"))
+            .respond_with(ResponseTemplate::new(409))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, Duration::from_millis(200));
+        let error = DataExporter::wait_for_export(&client, format!("{}/download", server.uri()))
+            .await
+            .unwrap_err();
+
+        match error {
+            ExporterError::Timeout { elapsed, status } => {
+                assert_eq!(StatusCode::CONFLICT, status);
+                // No wait goes past the deadline, only a slow last poll can end after it
+                assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+            }
+            e => panic!("Expected a timeout, got {e}"),
+        }
+        // Polled several times with backoff, not once and not continuously
+        let polls = server.received_requests().await.unwrap().len();
+        assert!((2..=10).contains(&polls), "{polls} polls");
+    }
+
+    #[tokio::test]
+    async fn retry_after_beyond_deadline_times_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/download"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "60"))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, Duration::from_secs(5));
+        let error = DataExporter::wait_for_export(&client, format!("{}/download", server.uri()))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            ExporterError::Timeout {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                ..
+            }
+        ));
+        assert_eq!(1, server.received_requests().await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn export_completes_after_pending() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/download"))
+            .respond_with(ResponseTemplate::new(409))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("done"))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, Duration::from_secs(5));
+        let response = DataExporter::wait_for_export(&client, format!("{}/download", server.uri()))
+            .await
+            .unwrap();
+
+        assert_eq!("done", response.text().await.unwrap());
+        assert_eq!(3, server.received_requests().await.unwrap().len());
+    }
+}
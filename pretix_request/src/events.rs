@@ -1,12 +1,12 @@
 use crate::organizer::OrganizerId;
 use crate::PretixClient;
 use reqwest::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
 use time::OffsetDateTime;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Event {
     /// The name of the event.
     /// Key is the language shortcode, e.g. `en`.
@@ -20,9 +20,8 @@ pub struct Event {
     pub date_to: Option<OffsetDateTime>,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Deserialize)]
-
-pub struct EventId(String);
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
+pub struct EventId(pub String);
 
 impl Event {
     /// List all events organized by the specified organizer.
@@ -31,6 +30,31 @@ impl Event {
             .list_paginated(client.url(format!("/api/v1/organizers/{organizer}/events")))
             .await
     }
+
+    /// The IANA timezone of the event, e.g. `Europe/Amsterdam`.
+    /// Pretix determines the day of an order in this timezone, e.g. for its reports.
+    pub async fn timezone(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        event: &EventId,
+    ) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Settings {
+            timezone: String,
+        }
+
+        let settings: Settings = client
+            .get(client.url(format!(
+                "/api/v1/organizers/{organizer}/events/{event}/settings/"
+            )))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(settings.timezone)
+    }
 }
 
 impl Display for EventId {
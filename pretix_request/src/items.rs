@@ -0,0 +1,42 @@
+use crate::data_exporter::as_f32;
+use crate::events::EventId;
+use crate::organizer::OrganizerId;
+use crate::PretixClient;
+use reqwest::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A product sold in an event.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Item {
+    pub id: u32,
+    /// The name of the item.
+    /// Key is the language shortcode, e.g. `en`.
+    /// Value is the name in the specified language.
+    pub name: HashMap<String, String>,
+    pub active: bool,
+    /// The ID of the category of the item
+    pub category: Option<u32>,
+    #[serde(deserialize_with = "as_f32")]
+    pub default_price: f32,
+    /// The VAT percentage, e.g. `21.00`
+    #[serde(deserialize_with = "as_f32")]
+    pub tax_rate: f32,
+    /// The ID of the tax rule of the item
+    pub tax_rule: Option<u32>,
+}
+
+impl Item {
+    /// List all items of an event.
+    pub async fn list(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        event: &EventId,
+    ) -> Result<Vec<Item>> {
+        client
+            .list_paginated(client.url(format!(
+                "/api/v1/organizers/{organizer}/events/{event}/items"
+            )))
+            .await
+    }
+}
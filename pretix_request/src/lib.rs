@@ -5,14 +5,19 @@ use reqwest::Client;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 
+use crate::data_exporter::ExportPolling;
+
 pub mod data_exporter;
 pub mod events;
+pub mod items;
 pub mod oauth;
+pub mod orders;
 pub mod organizer;
 
 pub struct PretixClient {
     client: Client,
     pretix_url: String,
+    export_polling: ExportPolling,
 }
 
 impl PretixClient {
@@ -30,7 +35,22 @@ impl PretixClient {
             .build()
             .expect("Creating Pretix request client");
 
-        Self { client, pretix_url }
+        Self {
+            client,
+            pretix_url,
+            export_polling: ExportPolling::default(),
+        }
+    }
+
+    /// Set how exports are polled until they are complete.
+    /// By default, [ExportPolling::default] is used.
+    pub fn set_export_polling(&mut self, export_polling: ExportPolling) {
+        self.export_polling = export_polling;
+    }
+
+    /// How exports are polled until they are complete.
+    pub fn export_polling(&self) -> &ExportPolling {
+        &self.export_polling
     }
 
     pub fn url<S: AsRef<str>>(&self, path: S) -> String {
@@ -41,7 +41,7 @@ where
     }
 
     reqwest::Client::new()
-        .post(&format!("{}/api/v1/oauth/token", pretix_uri.as_ref()))
+        .post(format!("{}/api/v1/oauth/token", pretix_uri.as_ref()))
         .basic_auth(client_id.as_ref(), Some(client_secret.as_ref()))
         .form(&RequestForm {
             code: &code,
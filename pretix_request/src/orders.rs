@@ -0,0 +1,58 @@
+use crate::data_exporter::as_f32;
+use crate::events::EventId;
+use crate::organizer::OrganizerId;
+use crate::PretixClient;
+use reqwest::Result;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize)]
+pub struct Order {
+    pub code: String,
+    /// The status of the order, e.g. `p` for paid.
+    pub status: String,
+    #[serde(deserialize_with = "as_f32")]
+    pub total: f32,
+    pub payments: Vec<OrderPayment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderPayment {
+    /// The ID of the payment within the order.
+    pub local_id: u32,
+    pub state: String,
+    #[serde(deserialize_with = "as_f32")]
+    pub amount: f32,
+    /// The payment provider, e.g. `mollie`.
+    pub provider: String,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub payment_date: Option<OffsetDateTime>,
+}
+
+impl Order {
+    /// Get an order by its code.
+    pub async fn get(
+        client: &PretixClient,
+        organizer: &OrganizerId,
+        event: &EventId,
+        code: &str,
+    ) -> Result<Self> {
+        client
+            .get(client.url(format!(
+                "/api/v1/organizers/{organizer}/events/{event}/orders/{code}/"
+            )))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
+
+impl OrderPayment {
+    /// Whether the payment was successful.
+    /// Refunded payments were successful at the time of payment.
+    pub fn is_successful(&self) -> bool {
+        self.state.eq("confirmed") || self.state.eq("refunded")
+    }
+}
@@ -1,16 +1,16 @@
 use reqwest::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
 use crate::PretixClient;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Organizer {
     pub name: String,
     pub slug: OrganizerId,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub struct OrganizerId(pub String);
 
 impl Organizer {